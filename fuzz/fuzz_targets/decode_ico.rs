@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+// Malformed input must come back as an error, never a panic or an
+// unbounded allocation.
+fuzz_target!(|data: &[u8]| {
+    _ = icon_extractor::decode_ico(data);
+});
@@ -0,0 +1,41 @@
+use crate::{extract_large_icon, ordinal};
+use anyhow::{Context, Result};
+use image::DynamicImage;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use tempfile::TempPath;
+
+/// Extracts the icon at `index` from an executable stored inside a ZIP
+/// archive. The entry is unpacked to a temporary file, which is removed
+/// again whether or not extraction succeeds.
+pub fn extract_icon_from_zip(zip_path: &Path, entry: &str, index: u32) -> Result<DynamicImage> {
+    let index = ordinal(index)?;
+    let temp_path = unpack_zip_entry(zip_path, entry)?;
+    Ok(extract_large_icon(&temp_path, index)?.into())
+}
+
+/// Copies `entry` out of the archive into a temporary file that keeps the
+/// entry's extension. The file is deleted when the returned path is dropped.
+pub fn unpack_zip_entry(zip_path: &Path, entry: &str) -> Result<TempPath> {
+    let file = File::open(zip_path)
+        .with_context(|| format!("Failed to open archive: {}", zip_path.display()))?;
+    let mut archive = zip::ZipArchive::new(file)
+        .with_context(|| format!("Failed to read archive: {}", zip_path.display()))?;
+    let mut zip_entry = archive
+        .by_name(entry)
+        .with_context(|| format!("No entry {entry} in {}", zip_path.display()))?;
+
+    let suffix = Path::new(entry)
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+    let mut temp_file = tempfile::Builder::new()
+        .prefix("icon_extractor_")
+        .suffix(&suffix)
+        .tempfile()?;
+    io::copy(&mut zip_entry, &mut temp_file)?;
+
+    // Close our handle so nothing holds the file open while Win32 reads it.
+    Ok(temp_file.into_temp_path())
+}
@@ -0,0 +1,158 @@
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+#[cfg(windows)]
+use std::ptr::null_mut;
+#[cfg(windows)]
+use widestring::{U16CStr, U16CString};
+#[cfg(windows)]
+use winapi::um::shellapi::{
+    SHFILEINFOW, SHGFI_ICON, SHGFI_LARGEICON, SHGFI_USEFILEATTRIBUTES, SHGetFileInfoW,
+};
+#[cfg(windows)]
+use winapi::um::winnt::FILE_ATTRIBUTE_NORMAL;
+#[cfg(windows)]
+use winapi::um::winreg::{HKEY_CLASSES_ROOT, RRF_RT_REG_SZ, RegGetValueW};
+#[cfg(windows)]
+use winapi::um::winuser::DestroyIcon;
+
+/// Extracts the icon the shell shows for files with extension `ext` (with or
+/// without the leading dot) and saves it as `<ext>.png` in `out_dir`. No file
+/// with that extension needs to exist.
+pub fn extract_extension_icon(ext: &str, out_dir: &Path) -> Result<PathBuf> {
+    let ext = ext.trim_start_matches('.');
+    if ext.is_empty() {
+        anyhow::bail!("Empty file extension.");
+    }
+
+    let img = extension_icon_image(ext)?;
+
+    let output_path = out_dir.join(format!("{ext}.png"));
+    img.save(&output_path)?;
+
+    Ok(output_path)
+}
+
+/// Extracts the icon for a MIME type such as `application/pdf`, using the file
+/// extension registered for it under `HKCR\MIME\Database\Content Type`. Saves
+/// it as `<ext>.png` in `out_dir`.
+pub fn extract_mime_icon(mime: &str, out_dir: &Path) -> Result<PathBuf> {
+    let ext = mime_extension(mime)?;
+    extract_extension_icon(&ext, out_dir)
+}
+
+#[cfg(windows)]
+fn extension_icon_image(ext: &str) -> Result<image::RgbaImage> {
+    let name = U16CString::from_str(format!(".{ext}"))?;
+
+    unsafe {
+        let mut info: SHFILEINFOW = std::mem::zeroed();
+        let ok = SHGetFileInfoW(
+            name.as_ptr(),
+            FILE_ATTRIBUTE_NORMAL,
+            &mut info,
+            std::mem::size_of::<SHFILEINFOW>() as u32,
+            SHGFI_ICON | SHGFI_LARGEICON | SHGFI_USEFILEATTRIBUTES,
+        );
+        if ok == 0 || info.hIcon.is_null() {
+            anyhow::bail!("SHGetFileInfoW failed for extension: .{ext}");
+        }
+
+        let img = crate::gdi::hicon_to_image(info.hIcon);
+        DestroyIcon(info.hIcon);
+        img
+    }
+}
+
+#[cfg(not(windows))]
+fn extension_icon_image(_ext: &str) -> Result<image::RgbaImage> {
+    Err(crate::IconError::UnsupportedPlatform.into())
+}
+
+/// The `Extension` value of `HKCR\MIME\Database\Content Type\<mime>`, without
+/// the leading dot.
+#[cfg(windows)]
+fn mime_extension(mime: &str) -> Result<String> {
+    let key = U16CString::from_str(format!(r"MIME\Database\Content Type\{mime}"))?;
+    let value = U16CString::from_str("Extension")?;
+    let missing = || anyhow::anyhow!("No file extension is registered for MIME type: {mime}");
+
+    unsafe {
+        let mut size = 0;
+        let status = RegGetValueW(
+            HKEY_CLASSES_ROOT,
+            key.as_ptr(),
+            value.as_ptr(),
+            RRF_RT_REG_SZ,
+            null_mut(),
+            null_mut(),
+            &mut size,
+        );
+        if status != 0 {
+            return Err(missing());
+        }
+
+        let mut buf = vec![0u16; (size as usize).div_ceil(2)];
+        let status = RegGetValueW(
+            HKEY_CLASSES_ROOT,
+            key.as_ptr(),
+            value.as_ptr(),
+            RRF_RT_REG_SZ,
+            null_mut(),
+            buf.as_mut_ptr().cast(),
+            &mut size,
+        );
+        if status != 0 {
+            return Err(missing());
+        }
+
+        let ext = U16CStr::from_slice_truncate(&buf)?.to_string_lossy();
+        let ext = ext.trim().trim_start_matches('.');
+        if ext.is_empty() {
+            return Err(missing());
+        }
+        Ok(ext.to_string())
+    }
+}
+
+#[cfg(not(windows))]
+fn mime_extension(_mime: &str) -> Result<String> {
+    Err(crate::IconError::UnsupportedPlatform.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_empty_extensions() {
+        let out_dir = tempfile::tempdir().unwrap();
+        for ext in ["", "."] {
+            let err = extract_extension_icon(ext, out_dir.path()).unwrap_err();
+            assert_eq!(err.to_string(), "Empty file extension.");
+        }
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn mime_icon_is_saved_under_its_extension() {
+        let out_dir = tempfile::tempdir().unwrap();
+        let path = extract_mime_icon("image/png", out_dir.path()).unwrap();
+
+        assert_eq!(path, out_dir.path().join("png.png"));
+        let img = image::open(&path).unwrap();
+        assert!(img.width() > 0 && img.height() > 0);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn unregistered_mime_types_are_reported() {
+        let out_dir = tempfile::tempdir().unwrap();
+        let err =
+            extract_mime_icon("application/x-icon-extractor-test", out_dir.path()).unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("No file extension is registered for MIME type"),
+            "{err:#}"
+        );
+    }
+}
@@ -0,0 +1,68 @@
+use crate::extract_icon_image;
+use anyhow::Result;
+use image::RgbaImage;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+
+/// How many background extractions may hold GDI objects at once. Each one
+/// needs a handful of handles, and a process only gets 10,000 by default.
+const MAX_CONCURRENT_EXTRACTIONS: usize = 8;
+
+/// A file to extract and where to send the result.
+type Job = (PathBuf, Sender<Result<RgbaImage>>);
+
+/// Feeds the worker pool, which is started on first use.
+static QUEUE: OnceLock<Sender<Job>> = OnceLock::new();
+
+/// Extracts the icon on a background thread and delivers the result on the
+/// returned channel, so UI threads can request icons without blocking.
+/// Requests are queued for a fixed pool of `MAX_CONCURRENT_EXTRACTIONS`
+/// worker threads.
+pub fn spawn_extract(file_path: PathBuf) -> Receiver<Result<RgbaImage>> {
+    let (tx, rx) = mpsc::channel();
+    // The workers never exit, so the queue always has a receiver.
+    _ = QUEUE.get_or_init(start_workers).send((file_path, tx));
+    rx
+}
+
+fn start_workers() -> Sender<Job> {
+    let (tx, rx) = mpsc::channel::<Job>();
+    let rx = Arc::new(Mutex::new(rx));
+
+    for i in 0..MAX_CONCURRENT_EXTRACTIONS {
+        let rx = Arc::clone(&rx);
+        thread::Builder::new()
+            .name(format!("icon-extract-{i}"))
+            .spawn(move || {
+                loop {
+                    // The lock is only held while waiting for the next job.
+                    let job = rx.lock().unwrap_or_else(|e| e.into_inner()).recv();
+                    let Ok((file_path, result_tx)) = job else {
+                        break;
+                    };
+                    _ = result_tx.send(extract_icon_image(&file_path, 0));
+                }
+            })
+            .expect("failed to spawn icon extraction worker");
+    }
+
+    tx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_queued_request_gets_a_result() {
+        let receivers: Vec<_> = (0..MAX_CONCURRENT_EXTRACTIONS * 3)
+            .map(|i| spawn_extract(PathBuf::from(format!("missing-{i}.exe"))))
+            .collect();
+
+        for rx in receivers {
+            assert!(rx.recv().unwrap().is_err());
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use crate::{ExtractionConfig, extract_icon_with_config};
+use anyhow::Result;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// Receives updates while [`extract_icons_batch`] works through its files, so
+/// callers can drive their own progress display.
+pub trait ProgressReporter {
+    /// Called after each file, successful or not. `current` counts from 1.
+    fn on_progress(&self, current: usize, total: usize, path: &Path, succeeded: bool);
+
+    /// Called when extracting or saving the icon of `path` fails, before the
+    /// matching `on_progress`.
+    fn on_error(&self, current: usize, total: usize, path: &Path, error: &anyhow::Error);
+}
+
+/// Prints `[current/total] path status` lines to stderr.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StderrProgressReporter;
+
+impl ProgressReporter for StderrProgressReporter {
+    fn on_progress(&self, current: usize, total: usize, path: &Path, succeeded: bool) {
+        // Failures already got their line from `on_error`.
+        if succeeded {
+            eprintln!("[{current}/{total}] {} done", path.display());
+        }
+    }
+
+    fn on_error(&self, current: usize, total: usize, path: &Path, error: &anyhow::Error) {
+        eprintln!("[{current}/{total}] {} failed: {error:#}", path.display());
+    }
+}
+
+/// Extracts the first icon of each file in `paths` and saves it as
+/// `<stem>.png` in `out_dir`, calling `config`'s `on_extracted` hook on each
+/// image first. When files share a stem, the later ones get a `_<n>` suffix
+/// rather than overwriting each other. Failures are passed to `reporter` and
+/// don't stop the batch; the paths written are returned in order.
+pub fn extract_icons_batch(
+    paths: &[PathBuf],
+    out_dir: &Path,
+    config: &ExtractionConfig,
+    reporter: &dyn ProgressReporter,
+) -> Vec<PathBuf> {
+    let mut names = HashSet::new();
+    let mut written = Vec::new();
+
+    for (i, path) in paths.iter().enumerate() {
+        let (current, total) = (i + 1, paths.len());
+        let succeeded = match extract_one(path, out_dir, config, &mut names) {
+            Ok(output_path) => {
+                written.push(output_path);
+                true
+            }
+            Err(e) => {
+                reporter.on_error(current, total, path, &e);
+                false
+            }
+        };
+        reporter.on_progress(current, total, path, succeeded);
+    }
+
+    written
+}
+
+fn extract_one(
+    path: &Path,
+    out_dir: &Path,
+    config: &ExtractionConfig,
+    names: &mut HashSet<String>,
+) -> Result<PathBuf> {
+    let img = extract_icon_with_config(path, 0, config)?;
+    if let Some(on_extracted) = &config.on_extracted {
+        on_extracted(path, &img);
+    }
+
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy())
+        .unwrap_or_else(|| "icon".into());
+    let output_path = out_dir.join(format!("{}.png", unique_name(names, &stem)));
+    img.save(&output_path)?;
+
+    Ok(output_path)
+}
+
+/// `base`, or `base_<n>` for the smallest `n` from 2 that isn't in `names`
+/// yet, and adds it there. Names are compared ignoring case, as Windows file
+/// systems do.
+fn unique_name(names: &mut HashSet<String>, base: &str) -> String {
+    let mut name = base.to_string();
+    for n in 2.. {
+        if names.insert(name.to_lowercase()) {
+            break;
+        }
+        name = format!("{base}_{n}");
+    }
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    /// Records every call as a line of text.
+    #[derive(Default)]
+    struct Recorder(RefCell<Vec<String>>);
+
+    impl ProgressReporter for Recorder {
+        fn on_progress(&self, current: usize, total: usize, path: &Path, succeeded: bool) {
+            self.0.borrow_mut().push(format!(
+                "progress {current}/{total} {} {succeeded}",
+                path.display()
+            ));
+        }
+
+        fn on_error(&self, current: usize, total: usize, path: &Path, _error: &anyhow::Error) {
+            self.0
+                .borrow_mut()
+                .push(format!("error {current}/{total} {}", path.display()));
+        }
+    }
+
+    #[test]
+    fn failures_are_reported_as_unsuccessful_progress() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = [dir.path().join("a.exe"), dir.path().join("b.exe")];
+        let reporter = Recorder::default();
+
+        let written =
+            extract_icons_batch(&paths, dir.path(), &ExtractionConfig::default(), &reporter);
+
+        assert!(written.is_empty());
+        let [a, b] = paths.map(|path| path.display().to_string());
+        assert_eq!(
+            reporter.0.into_inner(),
+            [
+                format!("error 1/2 {a}"),
+                format!("progress 1/2 {a} false"),
+                format!("error 2/2 {b}"),
+                format!("progress 2/2 {b} false"),
+            ]
+        );
+    }
+
+    #[test]
+    fn repeated_stems_get_a_suffix() {
+        let mut names = HashSet::new();
+        let unique: Vec<_> = ["app", "App", "setup", "app", "app_2"]
+            .iter()
+            .map(|base| unique_name(&mut names, base))
+            .collect();
+        assert_eq!(unique, ["app", "App_2", "setup", "app_3", "app_2_2"]);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn same_stem_in_one_batch_keeps_both_icons() {
+        let root = std::env::var_os("SystemRoot").expect("SystemRoot is set");
+        let shell32 = Path::new(&root).join(r"System32\shell32.dll");
+        let out_dir = tempfile::tempdir().unwrap();
+
+        let written = extract_icons_batch(
+            &[shell32.clone(), shell32],
+            out_dir.path(),
+            &ExtractionConfig::default(),
+            &Recorder::default(),
+        );
+
+        assert_eq!(
+            written,
+            [
+                out_dir.path().join("shell32.png"),
+                out_dir.path().join("shell32_2.png"),
+            ]
+        );
+        assert!(written.iter().all(|path| path.exists()));
+    }
+}
@@ -0,0 +1,37 @@
+use crate::{canonicalize, extract_icon_image};
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// Extracts the first icon to its [`stable_icon_path`], overwriting any
+/// previous extraction of the same file.
+pub fn extract_icon_to_cursor_dir(file_path: &Path) -> Result<PathBuf> {
+    let output_path = stable_icon_path(file_path, 0)?;
+    let img = extract_icon_image(file_path, 0)?;
+
+    if let Some(dir) = output_path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    img.save(&output_path)?;
+
+    Ok(output_path)
+}
+
+/// The deterministic location the icon at `index` is extracted to:
+/// `%LOCALAPPDATA%\Temp\icon_extractor\<sha256 of canonical path>_<index>.png`.
+/// Callers can check whether it exists to skip re-extracting.
+pub fn stable_icon_path(file_path: &Path, index: u32) -> Result<PathBuf> {
+    let canonical = canonicalize(file_path)?;
+    let digest = Sha256::digest(canonical.as_os_str().as_encoded_bytes());
+    let hash: String = digest.iter().map(|b| format!("{b:02x}")).collect();
+
+    Ok(cache_root().join(format!("{hash}_{index}.png")))
+}
+
+pub(crate) fn cache_root() -> PathBuf {
+    env::var_os("LOCALAPPDATA")
+        .map(|dir| PathBuf::from(dir).join("Temp"))
+        .unwrap_or_else(env::temp_dir)
+        .join("icon_extractor")
+}
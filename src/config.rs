@@ -0,0 +1,104 @@
+use image::DynamicImage;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub(crate) type PostProcess = Arc<dyn Fn(DynamicImage) -> DynamicImage + Send + Sync>;
+pub(crate) type OnExtracted = Arc<dyn Fn(&Path, &DynamicImage) + Send + Sync>;
+
+/// Options for [`extract_icon_with_config`](crate::extract_icon_with_config)
+/// and the functions built on it. Start from `ExtractionConfig::default()`
+/// and set what you need.
+#[derive(Clone, Default)]
+#[non_exhaustive]
+pub struct ExtractionConfig {
+    /// Gives up on extraction after this long. The worker thread is abandoned
+    /// (leaking whatever handles it holds) and a `TimedOut` error is returned.
+    pub extraction_timeout: Option<Duration>,
+    /// Places the icon at the center of a transparent canvas of this size,
+    /// scaling it down to fit when needed. Both sides must be non-zero.
+    pub canvas: Option<(u32, u32)>,
+    /// Converts the icon to grayscale (keeping alpha), e.g. for accessibility
+    /// previews. PNG output is then a two-channel gray+alpha image.
+    pub grayscale: bool,
+    /// Tags PNG output as sRGB. GDI renders icons in sRGB, so this only makes
+    /// color-managed viewers agree with everyone else.
+    pub srgb: bool,
+    /// With `srgb`, also writes the gAMA and cHRM chunks that stand in for
+    /// sRGB in decoders that don't understand it.
+    pub srgb_fallback_chunks: bool,
+    /// Writes PNG output as an indexed (palette) image. Icons with up to 256
+    /// colors keep them exactly; others are quantized to 256 colors.
+    pub indexed_png: bool,
+    /// Caps the palette of indexed PNG output at this many colors (2 to
+    /// 256, counting the one reserved for transparency), quantizing icons
+    /// that have more. Setting it implies `indexed_png`.
+    pub palette_size: Option<u16>,
+    /// Logs a `tracing` warning when the large icon comes back smaller than
+    /// 32x32, which means the file most likely only has a small icon.
+    pub warn_on_suspicious_icon: bool,
+    /// When the file has no icon resources, scans its raw bytes for embedded
+    /// PNG or ICO images instead of failing. Meant for files like
+    /// self-extracting archives; the result is a guess.
+    pub best_effort: bool,
+    /// How hard `OutputFormat::PngOptimized` works to shrink its output.
+    #[cfg(feature = "oxipng")]
+    pub oxipng_options: oxipng::Options,
+    pub(crate) post_process: Option<PostProcess>,
+    pub(crate) on_extracted: Option<OnExtracted>,
+}
+
+impl ExtractionConfig {
+    /// Runs `f` on the extracted image after the built-in transforms, right
+    /// before it is handed back or saved.
+    #[must_use]
+    pub fn with_post_process(
+        mut self,
+        f: impl Fn(DynamicImage) -> DynamicImage + Send + Sync + 'static,
+    ) -> Self {
+        self.post_process = Some(Arc::new(f));
+        self
+    }
+
+    /// Writes PNG output as an indexed image of at most `palette_size` colors
+    /// (at least 2, counting the transparent one); see `palette_size`.
+    #[must_use]
+    pub fn color_quantize(mut self, palette_size: u8) -> Self {
+        self.palette_size = Some(palette_size.into());
+        self
+    }
+
+    /// In batch mode, calls `f` with the source path and the image after each
+    /// successful extraction, before the image is saved.
+    #[must_use]
+    pub fn on_extracted(
+        mut self,
+        f: impl Fn(&Path, &DynamicImage) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_extracted = Some(Arc::new(f));
+        self
+    }
+}
+
+impl fmt::Debug for ExtractionConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("ExtractionConfig");
+        debug
+            .field("extraction_timeout", &self.extraction_timeout)
+            .field("canvas", &self.canvas)
+            .field("grayscale", &self.grayscale)
+            .field("srgb", &self.srgb)
+            .field("srgb_fallback_chunks", &self.srgb_fallback_chunks)
+            .field("indexed_png", &self.indexed_png)
+            .field("palette_size", &self.palette_size)
+            .field("warn_on_suspicious_icon", &self.warn_on_suspicious_icon)
+            .field("best_effort", &self.best_effort);
+        #[cfg(feature = "oxipng")]
+        debug.field("oxipng_options", &self.oxipng_options);
+        debug
+            .field("post_process", &self.post_process.as_ref().map(|_| ".."))
+            .field("on_extracted", &self.on_extracted.as_ref().map(|_| ".."))
+            .finish()
+    }
+}
@@ -0,0 +1,101 @@
+//! Image transforms applied by [`ExtractionConfig`](crate::ExtractionConfig)
+//! options, usable on their own.
+
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, GrayAlphaImage, LumaA, RgbaImage};
+
+/// Centers `img` on a transparent `width`x`height` canvas, shrinking it first
+/// (keeping its aspect ratio) if it doesn't fit. A canvas with a zero side
+/// gives an empty image.
+#[must_use]
+pub fn center_on_canvas(img: &DynamicImage, width: u32, height: u32) -> RgbaImage {
+    let fitted = if img.width() > width || img.height() > height {
+        img.resize(width, height, FilterType::Lanczos3)
+    } else {
+        img.clone()
+    };
+
+    let mut canvas = RgbaImage::new(width, height);
+    // Resizing never goes below 1px, so a zero-sized canvas can still be
+    // smaller than `fitted`.
+    let x = width.saturating_sub(fitted.width()) / 2;
+    let y = height.saturating_sub(fitted.height()) / 2;
+    imageops::replace(&mut canvas, &fitted.to_rgba8(), x as i64, y as i64);
+    canvas
+}
+
+/// Converts `img` to grayscale with Rec. 709 luminance weights, keeping the
+/// alpha channel.
+#[must_use]
+pub fn to_grayscale(img: &RgbaImage) -> GrayAlphaImage {
+    GrayAlphaImage::from_fn(img.width(), img.height(), |x, y| {
+        let [r, g, b, a] = img.get_pixel(x, y).0;
+        let luma = 0.2126 * r as f32 + 0.7152 * g as f32 + 0.0722 * b as f32;
+        LumaA([luma.round() as u8, a])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn center_on_canvas_shrinks_large_images_to_fit() {
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(128, 64, Rgba([255, 0, 0, 255])));
+        let canvas = center_on_canvas(&img, 32, 32);
+
+        assert_eq!(canvas.dimensions(), (32, 32));
+        // 128x64 fits as 32x16, leaving 8px bands above and below.
+        assert_eq!(canvas.get_pixel(16, 7)[3], 0);
+        assert_eq!(canvas.get_pixel(16, 8), &Rgba([255, 0, 0, 255]));
+        assert_eq!(canvas.get_pixel(16, 24)[3], 0);
+    }
+
+    #[test]
+    fn center_on_canvas_leaves_equal_margins() {
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(32, 32, Rgba([0, 0, 255, 255])));
+        let canvas = center_on_canvas(&img, 64, 64);
+
+        assert_eq!(canvas.dimensions(), (64, 64));
+        for (x, y, pixel) in canvas.enumerate_pixels() {
+            let inside = (16..48).contains(&x) && (16..48).contains(&y);
+            assert_eq!(pixel[3] == 255, inside, "pixel ({x}, {y})");
+        }
+    }
+
+    #[test]
+    fn center_on_canvas_accepts_zero_sizes() {
+        let img = DynamicImage::ImageRgba8(RgbaImage::new(32, 32));
+        assert_eq!(center_on_canvas(&img, 0, 0).dimensions(), (0, 0));
+        assert_eq!(center_on_canvas(&img, 0, 16).dimensions(), (0, 16));
+    }
+
+    #[test]
+    fn to_grayscale_keeps_size_and_alpha() {
+        let img = RgbaImage::from_fn(4, 2, |x, _| Rgba([10, 20, 30, x as u8 * 60]));
+        let gray = to_grayscale(&img);
+
+        assert_eq!(gray.dimensions(), (4, 2));
+        for (x, _, pixel) in gray.enumerate_pixels() {
+            assert_eq!(pixel[1], x as u8 * 60);
+        }
+    }
+
+    #[test]
+    fn to_grayscale_uses_rec_709_weights() {
+        let img = RgbaImage::from_fn(4, 1, |x, _| match x {
+            0 => Rgba([255, 0, 0, 255]),
+            1 => Rgba([0, 255, 0, 255]),
+            2 => Rgba([0, 0, 255, 255]),
+            _ => Rgba([200, 100, 50, 128]),
+        });
+        let gray = to_grayscale(&img);
+
+        assert_eq!(gray.get_pixel(0, 0), &LumaA([54, 255]));
+        assert_eq!(gray.get_pixel(1, 0), &LumaA([182, 255]));
+        assert_eq!(gray.get_pixel(2, 0), &LumaA([18, 255]));
+        // 0.2126 * 200 + 0.7152 * 100 + 0.0722 * 50 = 117.66
+        assert_eq!(gray.get_pixel(3, 0), &LumaA([118, 128]));
+    }
+}
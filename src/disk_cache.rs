@@ -0,0 +1,264 @@
+use crate::cache::cache_root;
+use crate::{OutputFormat, canonicalize, extract_icon_to_bytes};
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tempfile::NamedTempFile;
+
+/// Default limit on the total size of a [`DiskCache`].
+const DEFAULT_MAX_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Extracted icons kept on disk across process restarts, e.g. for a thumbnail
+/// service. Entries are keyed by the source's path, modification time and
+/// size, so a rebuilt executable is extracted afresh. Once the directory
+/// grows past its size limit, the least recently used entries are removed.
+#[derive(Debug, Clone)]
+pub struct DiskCache {
+    dir: PathBuf,
+    max_size: u64,
+}
+
+impl Default for DiskCache {
+    /// A 64 MiB cache in `icon_extractor\cache` under the temp directory.
+    fn default() -> Self {
+        DiskCache::new(cache_root().join("cache"))
+    }
+}
+
+impl DiskCache {
+    /// A cache in `dir` with the default 64 MiB limit. The directory is
+    /// created on first use.
+    #[must_use]
+    pub fn new(dir: impl Into<PathBuf>) -> DiskCache {
+        DiskCache {
+            dir: dir.into(),
+            max_size: DEFAULT_MAX_SIZE,
+        }
+    }
+
+    /// Sets the total size, in bytes, the cache directory may reach before
+    /// entries are evicted.
+    #[must_use]
+    pub fn with_max_size(mut self, max_size: u64) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// The directory entries are stored in.
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Returns the cached file for the icon at `index` in `format`,
+    /// extracting and storing it first if there is no valid entry.
+    pub fn extract(&self, file_path: &Path, index: u32, format: OutputFormat) -> Result<PathBuf> {
+        self.get_or_insert_with(file_path, index, format, || {
+            extract_icon_to_bytes(file_path, index, format)
+        })
+    }
+
+    /// `extract`, with `encode` producing the bytes of a missing entry.
+    fn get_or_insert_with(
+        &self,
+        file_path: &Path,
+        index: u32,
+        format: OutputFormat,
+        encode: impl FnOnce() -> Result<Vec<u8>>,
+    ) -> Result<PathBuf> {
+        let entry = self.entry_path(file_path, index, format)?;
+        if entry.is_file() {
+            // Marks the entry as recently used.
+            _ = File::options()
+                .write(true)
+                .open(&entry)
+                .and_then(|file| file.set_modified(SystemTime::now()));
+            return Ok(entry);
+        }
+
+        let bytes = encode()?;
+        fs::create_dir_all(&self.dir)?;
+        // Written under a temporary name first so other processes never see a
+        // partial entry.
+        let mut temp_file = NamedTempFile::new_in(&self.dir)?;
+        std::io::Write::write_all(&mut temp_file, &bytes)?;
+        temp_file.persist(&entry)?;
+
+        self.evict(&entry)?;
+        Ok(entry)
+    }
+
+    fn entry_path(&self, file_path: &Path, index: u32, format: OutputFormat) -> Result<PathBuf> {
+        let canonical = canonicalize(file_path)?;
+        let metadata = fs::metadata(&canonical)?;
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+
+        let mut hasher = Sha256::new();
+        hasher.update(canonical.as_os_str().as_encoded_bytes());
+        hasher.update(modified.to_le_bytes());
+        hasher.update(metadata.len().to_le_bytes());
+        hasher.update(index.to_le_bytes());
+        hasher.update(format.extension());
+        let hash: String = hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+
+        Ok(self.dir.join(format!("{hash}.{}", format.extension())))
+    }
+
+    /// Deletes the least recently used entries other than `keep` until the
+    /// cache fits its size limit.
+    fn evict(&self, keep: &Path) -> Result<()> {
+        let mut entries = Vec::new();
+        let mut total = 0;
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            // Skips other processes' entries that are still being written.
+            if !metadata.is_file() || entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            total += metadata.len();
+            entries.push((metadata.modified()?, metadata.len(), entry.path()));
+        }
+
+        entries.sort_unstable();
+        for (_, len, path) in entries {
+            if total <= self.max_size {
+                break;
+            }
+            if path == keep {
+                continue;
+            }
+            // Another process may have removed it already.
+            if fs::remove_file(&path).is_ok() {
+                total -= len;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::time::Duration;
+
+    fn set_modified(path: &Path, time: SystemTime) {
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(time)
+            .unwrap();
+    }
+
+    #[test]
+    fn separate_instances_share_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("app.exe");
+        fs::write(&source, b"MZ").unwrap();
+        let cache_dir = dir.path().join("cache");
+        let encodes = Cell::new(0);
+        let encode = || {
+            encodes.set(encodes.get() + 1);
+            Ok(b"png bytes".to_vec())
+        };
+
+        // Each instance stands in for a separate process.
+        let first = DiskCache::new(&cache_dir)
+            .get_or_insert_with(&source, 0, OutputFormat::Png, encode)
+            .unwrap();
+        let second = DiskCache::new(&cache_dir)
+            .get_or_insert_with(&source, 0, OutputFormat::Png, encode)
+            .unwrap();
+
+        assert_eq!(first, second);
+        assert_eq!(first.parent(), Some(cache_dir.as_path()));
+        assert_eq!(encodes.get(), 1);
+        assert_eq!(fs::read(&second).unwrap(), b"png bytes");
+    }
+
+    #[test]
+    fn key_covers_modification_time_index_and_format() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("app.exe");
+        fs::write(&source, b"MZ").unwrap();
+        let cache = DiskCache::new(dir.path().join("cache"));
+        let key = || cache.entry_path(&source, 0, OutputFormat::Png).unwrap();
+
+        let before = key();
+        assert_eq!(key(), before);
+        assert_ne!(
+            cache.entry_path(&source, 1, OutputFormat::Png).unwrap(),
+            before
+        );
+        assert_ne!(
+            cache.entry_path(&source, 0, OutputFormat::Qoi).unwrap(),
+            before
+        );
+
+        set_modified(&source, SystemTime::now() - Duration::from_secs(3600));
+        assert_ne!(key(), before);
+    }
+
+    #[test]
+    fn evict_removes_least_recently_used_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = DiskCache::new(dir.path()).with_max_size(25);
+        let now = SystemTime::now();
+        let entry = |name: &str, age_secs: u64| {
+            let path = dir.path().join(name);
+            fs::write(&path, [0; 10]).unwrap();
+            set_modified(&path, now - Duration::from_secs(age_secs));
+            path
+        };
+        let oldest = entry("oldest.png", 400);
+        let kept = entry("kept.png", 300);
+        let old = entry("old.png", 200);
+        let new = entry("new.png", 100);
+        // A temporary file another process is still writing.
+        let partial = entry(".tmpAbCd12", 500);
+
+        cache.evict(&kept).unwrap();
+
+        // 40 bytes of entries; dropping the two oldest others gets to 20.
+        assert!(!oldest.exists());
+        assert!(kept.exists());
+        assert!(!old.exists());
+        assert!(new.exists());
+        assert!(partial.exists());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn second_extraction_is_served_from_the_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let system_root = std::env::var_os("SystemRoot").unwrap();
+        let shell32 = Path::new(&system_root).join(r"System32\shell32.dll");
+
+        let first = DiskCache::new(dir.path())
+            .extract(&shell32, 0, OutputFormat::Png)
+            .unwrap();
+        // Backdate the entry; a cache hit marks it as used again.
+        let old = SystemTime::now() - Duration::from_secs(3600);
+        set_modified(&first, old);
+        let second = DiskCache::new(dir.path())
+            .extract(&shell32, 0, OutputFormat::Png)
+            .unwrap();
+
+        assert_eq!(first, second);
+        assert!(fs::metadata(&second).unwrap().modified().unwrap() > old);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+        assert!(fs::read(&second).unwrap().starts_with(b"\x89PNG"));
+    }
+}
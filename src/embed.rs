@@ -0,0 +1,107 @@
+use crate::{ExtractionConfig, OutputFormat, extract_icon_image, extract_icon_to_bytes};
+use anyhow::Result;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use image::DynamicImage;
+use std::path::Path;
+
+/// Wraps the first icon, as a base64 PNG, in a minimal SVG document sized to
+/// the icon. This is a raster inside an SVG container, not a vectorization;
+/// it lets the icon be scaled with CSS like any other SVG.
+pub fn extract_icon_svg_wrapped(file_path: &Path) -> Result<String> {
+    let img: DynamicImage = extract_icon_image(file_path, 0)?.into();
+    let png = OutputFormat::Png.encode(&img, &ExtractionConfig::default())?;
+    Ok(svg_wrap(&png, img.width(), img.height()))
+}
+
+/// A minimal SVG document of `width`x`height` showing `png`.
+fn svg_wrap(png: &[u8], width: u32, height: u32) -> String {
+    format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}"><image width="{width}" height="{height}" href="{}"/></svg>"#,
+        png_data_uri(png)
+    )
+}
+
+/// Extracts the icon at `index` as a `data:image/png;base64,...` URI, for
+/// inlining in HTML `<img src>` or CSS `url(...)`.
+pub fn extract_icon_as_data_uri(file_path: &Path, index: u32) -> Result<String> {
+    let png = extract_icon_to_bytes(file_path, index, OutputFormat::Png)?;
+    Ok(png_data_uri(&png))
+}
+
+/// Extracts the icon at `index` as an `<img />` tag with the image inlined
+/// as a data URI and its size set, so HTML reports need no separate files.
+/// `alt` is HTML-escaped.
+pub fn extract_icon_as_html_img_tag(file_path: &Path, index: u32, alt: &str) -> Result<String> {
+    let img: DynamicImage = extract_icon_image(file_path, index)?.into();
+    let png = OutputFormat::Png.encode(&img, &ExtractionConfig::default())?;
+
+    Ok(format!(
+        r#"<img src="{}" alt="{}" width="{}" height="{}" />"#,
+        png_data_uri(&png),
+        escape_html(alt),
+        img.width(),
+        img.height()
+    ))
+}
+
+/// Escapes `text` for use in HTML content or a quoted attribute value.
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn png_data_uri(png: &[u8]) -> String {
+    format!("data:image/png;base64,{}", STANDARD.encode(png))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_html_escapes_markup_and_quotes() {
+        assert_eq!(
+            escape_html(r#"Tom & "Jerry's" <App>"#),
+            "Tom &amp; &quot;Jerry&#39;s&quot; &lt;App&gt;"
+        );
+        assert_eq!(escape_html("plain text"), "plain text");
+    }
+
+    #[test]
+    fn svg_wrap_embeds_the_png() {
+        let png = b"\x89PNG\r\n\x1a\nnot really";
+        let svg = svg_wrap(png, 48, 32);
+
+        let doc = roxmltree::Document::parse(&svg).unwrap();
+        let root = doc.root_element();
+        assert_eq!(root.tag_name().name(), "svg");
+        assert_eq!(
+            root.tag_name().namespace(),
+            Some("http://www.w3.org/2000/svg")
+        );
+        assert_eq!(root.attribute("width"), Some("48"));
+        assert_eq!(root.attribute("height"), Some("32"));
+        assert_eq!(root.attribute("viewBox"), Some("0 0 48 32"));
+
+        let image = root.first_element_child().unwrap();
+        assert_eq!(image.tag_name().name(), "image");
+        assert_eq!(image.attribute("width"), Some("48"));
+        assert_eq!(image.attribute("height"), Some("32"));
+        let payload = image
+            .attribute("href")
+            .and_then(|href| href.strip_prefix("data:image/png;base64,"))
+            .unwrap();
+        assert_eq!(STANDARD.decode(payload).unwrap(), png);
+    }
+}
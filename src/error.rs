@@ -0,0 +1,185 @@
+use crate::ExtractionMethod;
+use std::fmt;
+use std::path::PathBuf;
+
+/// Failures callers may want to tell apart. These are returned inside
+/// `anyhow::Error`; use `downcast_ref::<IconError>()` to inspect them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum IconError {
+    /// The file doesn't exist.
+    FileNotFound(PathBuf),
+    /// The file isn't a PE image (or doesn't have a PE file extension), so it
+    /// can't carry icon resources.
+    NotPeFile(PathBuf),
+    /// The file exists but couldn't be opened, e.g. because of permissions or
+    /// another process holding it exclusively. Retrying may help.
+    AccessDenied(PathBuf),
+    /// The file has no icon at this index. Negative indices are resource IDs.
+    NoIcon {
+        /// The file that was searched.
+        path: PathBuf,
+        /// The index that was asked for.
+        index: i32,
+        /// How many icons the file has, when that is known.
+        count: Option<u32>,
+    },
+    /// The icon bitmap reports a size no real icon has, which usually means
+    /// a corrupt resource.
+    UnreasonableDimensions {
+        /// Width the bitmap reports.
+        width: i32,
+        /// Height the bitmap reports.
+        height: i32,
+    },
+    /// A Win32 call failed with an error code not covered by another variant.
+    Win32 {
+        /// The file being read.
+        path: PathBuf,
+        /// The `GetLastError` code.
+        code: u32,
+    },
+    /// An ICO file (or icon data in ICO layout) is truncated or inconsistent.
+    MalformedIco(String),
+    /// The operation is built on Win32 and this isn't Windows.
+    UnsupportedPlatform,
+}
+
+/// Messages end with a hint on what to try next, where there is one.
+impl fmt::Display for IconError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IconError::FileNotFound(path) => write!(
+                f,
+                "File not found: {}; check the path and that the file still exists",
+                path.display()
+            ),
+            IconError::NotPeFile(path) => write!(
+                f,
+                "The provided file is not a valid executable: {}; icons can only be read from PE files (.exe, .dll, .cpl, .ocx, .scr, .mui)",
+                path.display()
+            ),
+            IconError::AccessDenied(path) => write!(
+                f,
+                "Access denied: {}; check its permissions or close programs that have it open, then retry",
+                path.display()
+            ),
+            IconError::NoIcon { path, index, count } => {
+                if *index < 0 {
+                    let id = index.unsigned_abs();
+                    write!(f, "No icon with resource ID {id} in {}", path.display())?;
+                } else {
+                    write!(f, "No icon at index {index} in {}", path.display())?;
+                }
+                match count {
+                    Some(0) => write!(f, " (file contains no icons)"),
+                    Some(1) => write!(f, " (file contains 1 icon; the only valid index is 0)"),
+                    Some(n) => write!(
+                        f,
+                        " (file contains {n} icons; valid indices are 0\u{2013}{})",
+                        n - 1
+                    ),
+                    None => Ok(()),
+                }
+            }
+            IconError::UnreasonableDimensions { width, height } => write!(
+                f,
+                "Icon bitmap has unreasonable dimensions: {width}x{height}; the icon resource is probably corrupt"
+            ),
+            IconError::Win32 { path, code } => {
+                write!(f, "Win32 error {code:#x}")?;
+                if let Some(name) = win32_error_name(*code) {
+                    write!(f, " ({name})")?;
+                }
+                write!(
+                    f,
+                    " while reading {}; verify the file is a valid PE executable",
+                    path.display()
+                )
+            }
+            IconError::MalformedIco(reason) => write!(
+                f,
+                "Malformed ICO file: {reason}; the file is truncated, corrupt or not an icon"
+            ),
+            IconError::UnsupportedPlatform => {
+                write!(f, "This operation is only supported on Windows")
+            }
+        }
+    }
+}
+
+/// Names of the Win32 error codes icon extraction runs into that don't have
+/// a variant of their own.
+fn win32_error_name(code: u32) -> Option<&'static str> {
+    Some(match code {
+        0x8 => "ERROR_NOT_ENOUGH_MEMORY",
+        0xD => "ERROR_INVALID_DATA",
+        0xE => "ERROR_OUTOFMEMORY",
+        0x15 => "ERROR_NOT_READY",
+        0x1E => "ERROR_READ_FAULT",
+        0x57 => "ERROR_INVALID_PARAMETER",
+        0x7B => "ERROR_INVALID_NAME",
+        0x3EE => "ERROR_FILE_INVALID",
+        0x5AA => "ERROR_NO_SYSTEM_RESOURCES",
+        _ => return None,
+    })
+}
+
+impl std::error::Error for IconError {}
+
+/// Every [`ExtractionMethod`] given to `extract_icon_with_fallback_chain`
+/// failed; holds why each one did, in the order they were tried.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct AggregateError {
+    /// The file icons were extracted from.
+    pub path: PathBuf,
+    /// Each method that was tried, with the error it failed with.
+    pub failures: Vec<(ExtractionMethod, anyhow::Error)>,
+}
+
+impl fmt::Display for AggregateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "All {} extraction methods failed for {}",
+            self.failures.len(),
+            self.path.display()
+        )?;
+        for (method, error) in &self.failures {
+            write!(f, "\n  {method}: {error:#}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for AggregateError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aggregate_error_lists_every_failure() {
+        let error = AggregateError {
+            path: PathBuf::from(r"C:\app.exe"),
+            failures: vec![
+                (
+                    ExtractionMethod::ExtractIconEx,
+                    anyhow::anyhow!("no icon").context("ExtractIconExW failed"),
+                ),
+                (
+                    ExtractionMethod::SHGetFileInfo,
+                    anyhow::anyhow!("SHGetFileInfoW returned no icon"),
+                ),
+            ],
+        };
+
+        assert_eq!(
+            error.to_string(),
+            "All 2 extraction methods failed for C:\\app.exe\n  \
+             ExtractIconExW: ExtractIconExW failed: no icon\n  \
+             SHGetFileInfoW: SHGetFileInfoW returned no icon"
+        );
+    }
+}
@@ -0,0 +1,182 @@
+use crate::{AggregateError, extract_icon_at_size, extract_icon_image};
+#[cfg(windows)]
+use crate::{check_icon_file, gdi, ordinal, to_extended_path};
+use anyhow::Result;
+use image::{DynamicImage, RgbaImage};
+use std::fmt;
+use std::path::Path;
+#[cfg(windows)]
+use winapi::shared::minwindef::MAX_PATH;
+#[cfg(windows)]
+use winapi::um::shellapi::{SHFILEINFOW, SHGFI_ICON, SHGFI_LARGEICON, SHGetFileInfoW};
+#[cfg(windows)]
+use winapi::um::winuser::{DestroyIcon, GetSystemMetrics, SM_CXICON};
+#[cfg(windows)]
+use windows::Win32::UI::WindowsAndMessaging::{HICON, PrivateExtractIconsW};
+
+/// A Win32 API icons can be extracted with. They disagree on which files they
+/// accept, so trying several helps with unusual executables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ExtractionMethod {
+    /// `ExtractIconExW`, what [`extract_icon_image`] uses.
+    ExtractIconEx,
+    /// `SHDefExtractIconW`, the shell's default extractor, at the system's
+    /// large icon size.
+    SHDefExtractIcon,
+    /// `PrivateExtractIconsW`, which also reads 16-bit NE executables.
+    PrivateExtractIcons,
+    /// `SHGetFileInfoW`, the icon Explorer shows for the file. This ignores
+    /// the index.
+    SHGetFileInfo,
+}
+
+impl ExtractionMethod {
+    /// Every method, in the order `extract_icon_with_fallback_chain` is
+    /// usually given them.
+    pub const ALL: &[ExtractionMethod] = &[
+        ExtractionMethod::ExtractIconEx,
+        ExtractionMethod::SHDefExtractIcon,
+        ExtractionMethod::PrivateExtractIcons,
+        ExtractionMethod::SHGetFileInfo,
+    ];
+}
+
+impl fmt::Display for ExtractionMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ExtractionMethod::ExtractIconEx => "ExtractIconExW",
+            ExtractionMethod::SHDefExtractIcon => "SHDefExtractIconW",
+            ExtractionMethod::PrivateExtractIcons => "PrivateExtractIconsW",
+            ExtractionMethod::SHGetFileInfo => "SHGetFileInfoW",
+        })
+    }
+}
+
+/// Extracts the icon at `index` with each of `methods` in turn and returns
+/// the first success. If they all fail, the error is an [`AggregateError`]
+/// saying why each one did.
+pub fn extract_icon_with_fallback_chain(
+    file_path: &Path,
+    index: u32,
+    methods: &[ExtractionMethod],
+) -> Result<DynamicImage> {
+    if methods.is_empty() {
+        anyhow::bail!("No extraction methods given.");
+    }
+
+    let mut failures = Vec::new();
+    for &method in methods {
+        match extract_with(method, file_path, index) {
+            Ok(img) => return Ok(img.into()),
+            Err(e) => {
+                tracing::debug!("{method} failed for {}: {e:#}", file_path.display());
+                failures.push((method, e));
+            }
+        }
+    }
+
+    Err(AggregateError {
+        path: file_path.to_path_buf(),
+        failures,
+    }
+    .into())
+}
+
+fn extract_with(method: ExtractionMethod, file_path: &Path, index: u32) -> Result<RgbaImage> {
+    match method {
+        ExtractionMethod::ExtractIconEx => extract_icon_image(file_path, index),
+        ExtractionMethod::SHDefExtractIcon => {
+            extract_icon_at_size(file_path, index, large_icon_size())
+        }
+        ExtractionMethod::PrivateExtractIcons => private_extract_icon(file_path, index),
+        ExtractionMethod::SHGetFileInfo => shell_file_icon(file_path),
+    }
+}
+
+#[cfg(windows)]
+fn large_icon_size() -> u32 {
+    unsafe { GetSystemMetrics(SM_CXICON) as u32 }
+}
+
+#[cfg(not(windows))]
+fn large_icon_size() -> u32 {
+    32
+}
+
+#[cfg(windows)]
+fn private_extract_icon(file_path: &Path, index: u32) -> Result<RgbaImage> {
+    let wide = to_extended_path(&check_icon_file(file_path)?);
+    // The binding only takes a MAX_PATH array, NUL included, so longer paths
+    // can't be passed without the API reading past its end.
+    if wide.len() > MAX_PATH {
+        anyhow::bail!(
+            "PrivateExtractIconsW only takes paths of up to {} characters: {}",
+            MAX_PATH - 1,
+            file_path.display()
+        );
+    }
+    let mut file_str = [0u16; MAX_PATH];
+    file_str[..wide.len()].copy_from_slice(&wide);
+    let size = large_icon_size() as i32;
+    let ordinal = ordinal(index)?;
+
+    unsafe {
+        let mut hicon = [HICON::default()];
+        let extracted =
+            PrivateExtractIconsW(&file_str, ordinal, size, size, Some(&mut hicon), None, 0);
+        // 0xFFFFFFFF means the file couldn't be read at all.
+        if extracted == 0 || extracted == u32::MAX || hicon[0].is_invalid() {
+            anyhow::bail!("PrivateExtractIconsW found no icon at index {index}");
+        }
+
+        let hicon = hicon[0].0 as _;
+        let img = gdi::hicon_to_image(hicon);
+        DestroyIcon(hicon);
+        img
+    }
+}
+
+#[cfg(not(windows))]
+fn private_extract_icon(_file_path: &Path, _index: u32) -> Result<RgbaImage> {
+    Err(crate::IconError::UnsupportedPlatform.into())
+}
+
+#[cfg(windows)]
+fn shell_file_icon(file_path: &Path) -> Result<RgbaImage> {
+    let file_str = to_extended_path(&check_icon_file(file_path)?);
+
+    unsafe {
+        let mut info: SHFILEINFOW = std::mem::zeroed();
+        let ok = SHGetFileInfoW(
+            file_str.as_ptr(),
+            0,
+            &mut info,
+            std::mem::size_of::<SHFILEINFOW>() as u32,
+            SHGFI_ICON | SHGFI_LARGEICON,
+        );
+        if ok == 0 || info.hIcon.is_null() {
+            anyhow::bail!("SHGetFileInfoW returned no icon");
+        }
+
+        let img = gdi::hicon_to_image(info.hIcon);
+        DestroyIcon(info.hIcon);
+        img
+    }
+}
+
+#[cfg(not(windows))]
+fn shell_file_icon(_file_path: &Path) -> Result<RgbaImage> {
+    Err(crate::IconError::UnsupportedPlatform.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_method_list_is_rejected() {
+        let err = extract_icon_with_fallback_chain(Path::new("app.exe"), 0, &[]).unwrap_err();
+        assert_eq!(err.to_string(), "No extraction methods given.");
+    }
+}
@@ -0,0 +1,53 @@
+use crate::size::render_from_native;
+use anyhow::Result;
+use image::ExtendedColorType;
+use image::codecs::ico::{IcoEncoder, IcoFrame};
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+/// The PNG sizes of a web favicon set: browser tabs (16, 32), Windows tiles
+/// (48, 64, 128), Apple touch icons (180) and Android (192).
+const FAVICON_SIZES: &[u32] = &[16, 32, 48, 64, 128, 180, 192];
+
+/// The sizes embedded in `favicon.ico`, for browsers that only look there.
+const FAVICON_ICO_SIZES: &[u32] = &[16, 32];
+
+/// Writes a complete web favicon set for the first icon into `output_dir`:
+/// `favicon-<n>x<n>.png` at each standard size and a `favicon.ico` holding
+/// the 16px and 32px images. Each size is scaled from the best native image,
+/// as in `extract_icon_quality`.
+///
+/// Returns the path of each PNG keyed by its size. `favicon.ico` holds
+/// several sizes, so it isn't in the map; it is always
+/// `output_dir.join("favicon.ico")`.
+pub fn generate_favicon_set(file_path: &Path, output_dir: &Path) -> Result<HashMap<u32, PathBuf>> {
+    let mut written = HashMap::new();
+    let mut ico_images = Vec::new();
+
+    for &size in FAVICON_SIZES {
+        let img = render_from_native(file_path, size)?;
+        let png_path = output_dir.join(format!("favicon-{size}x{size}.png"));
+        img.save(&png_path)?;
+        written.insert(size, png_path);
+
+        if FAVICON_ICO_SIZES.contains(&size) {
+            ico_images.push(img);
+        }
+    }
+
+    let frames = ico_images
+        .iter()
+        .map(|img| {
+            IcoFrame::as_png(
+                img.as_raw(),
+                img.width(),
+                img.height(),
+                ExtendedColorType::Rgba8,
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    IcoEncoder::new(File::create(output_dir.join("favicon.ico"))?).encode_images(&frames)?;
+
+    Ok(written)
+}
@@ -0,0 +1,351 @@
+use crate::{ExtractionConfig, palette};
+use anyhow::Result;
+use image::{DynamicImage, ImageFormat, Rgb, RgbImage, RgbaImage};
+use std::io::Cursor;
+
+/// An image format icons can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum OutputFormat {
+    /// The default, lossless with alpha.
+    #[default]
+    Png,
+    /// Lossless like PNG, but much cheaper to encode and decode.
+    Qoi,
+    /// A single-image Windows icon file.
+    Ico,
+    /// No alpha channel, so the icon is flattened onto a white background.
+    Jpeg,
+    /// PNG passed through oxipng with `ExtractionConfig::oxipng_options`:
+    /// slower to encode, but smaller with identical pixels.
+    #[cfg(feature = "oxipng")]
+    PngOptimized,
+}
+
+impl OutputFormat {
+    /// The usual file extension, without the dot.
+    #[must_use]
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Png => "png",
+            OutputFormat::Qoi => "qoi",
+            OutputFormat::Ico => "ico",
+            OutputFormat::Jpeg => "jpg",
+            #[cfg(feature = "oxipng")]
+            OutputFormat::PngOptimized => "png",
+        }
+    }
+
+    /// Looks up the format for a file extension (without the dot).
+    #[must_use]
+    pub fn from_extension(ext: &str) -> Option<OutputFormat> {
+        let format = match ext.to_ascii_lowercase().as_str() {
+            "png" => OutputFormat::Png,
+            "qoi" => OutputFormat::Qoi,
+            "ico" => OutputFormat::Ico,
+            "jpg" | "jpeg" => OutputFormat::Jpeg,
+            _ => return None,
+        };
+        Some(format)
+    }
+
+    /// Encodes `img`, applying the output options in `config`.
+    pub fn encode(self, img: &DynamicImage, config: &ExtractionConfig) -> Result<Vec<u8>> {
+        match self {
+            OutputFormat::Png => encode_png(img, config),
+            OutputFormat::Qoi => {
+                let rgba = img.to_rgba8();
+                Ok(qoi::encode_to_vec(
+                    rgba.as_raw(),
+                    rgba.width(),
+                    rgba.height(),
+                )?)
+            }
+            OutputFormat::Ico => write_image(img, ImageFormat::Ico),
+            OutputFormat::Jpeg => {
+                let flat = flatten(&img.to_rgba8(), Rgb([255, 255, 255]));
+                write_image(&flat.into(), ImageFormat::Jpeg)
+            }
+            #[cfg(feature = "oxipng")]
+            OutputFormat::PngOptimized => Ok(oxipng::optimize_from_memory(
+                &encode_png(img, config)?,
+                &config.oxipng_options,
+            )?),
+        }
+    }
+}
+
+fn write_image(img: &DynamicImage, format: ImageFormat) -> Result<Vec<u8>> {
+    let mut buf = Cursor::new(Vec::new());
+    img.write_to(&mut buf, format)?;
+    Ok(buf.into_inner())
+}
+
+fn encode_png(img: &DynamicImage, config: &ExtractionConfig) -> Result<Vec<u8>> {
+    if config.indexed_png || config.palette_size.is_some() {
+        return encode_indexed_png(&img.to_rgba8(), config);
+    }
+
+    let (color_type, img) = match img {
+        DynamicImage::ImageLuma8(_) => (png::ColorType::Grayscale, img.clone()),
+        DynamicImage::ImageLumaA8(_) => (png::ColorType::GrayscaleAlpha, img.clone()),
+        DynamicImage::ImageRgb8(_) => (png::ColorType::Rgb, img.clone()),
+        _ => (png::ColorType::Rgba, img.to_rgba8().into()),
+    };
+
+    let mut buf = Vec::new();
+    let mut encoder = png::Encoder::new(&mut buf, img.width(), img.height());
+    encoder.set_color(color_type);
+    encoder.set_depth(png::BitDepth::Eight);
+    set_color_space(&mut encoder, config);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(img.as_bytes())?;
+    writer.finish()?;
+
+    Ok(buf)
+}
+
+fn encode_indexed_png(img: &RgbaImage, config: &ExtractionConfig) -> Result<Vec<u8>> {
+    let max_colors = config
+        .palette_size
+        .map_or(palette::MAX_COLORS, |size| size as usize);
+    let indexed = palette::index_image(img, max_colors);
+    let rgb: Vec<u8> = indexed
+        .palette
+        .iter()
+        .flat_map(|c| [c[0], c[1], c[2]])
+        .collect();
+    let alpha: Vec<u8> = indexed.palette.iter().map(|c| c[3]).collect();
+
+    let mut buf = Vec::new();
+    let mut encoder = png::Encoder::new(&mut buf, img.width(), img.height());
+    let depth = match indexed.palette.len() {
+        0..=2 => png::BitDepth::One,
+        3..=4 => png::BitDepth::Two,
+        5..=16 => png::BitDepth::Four,
+        _ => png::BitDepth::Eight,
+    };
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(depth);
+    encoder.set_palette(rgb);
+    encoder.set_trns(alpha);
+    set_color_space(&mut encoder, config);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&pack_indices(
+        &indexed.indices,
+        img.width() as usize,
+        depth as usize,
+    ))?;
+    writer.finish()?;
+
+    Ok(buf)
+}
+
+/// Packs one-byte palette indices into rows of `bits`-wide samples, most
+/// significant first, as PNG stores them.
+fn pack_indices(indices: &[u8], width: usize, bits: usize) -> Vec<u8> {
+    if bits == 8 {
+        return indices.to_vec();
+    }
+    let row_len = (width * bits).div_ceil(8);
+    let mut packed = vec![0; row_len * indices.len().div_ceil(width.max(1))];
+    for (row, out) in indices.chunks(width).zip(packed.chunks_exact_mut(row_len)) {
+        for (x, &index) in row.iter().enumerate() {
+            let bit = x * bits;
+            out[bit / 8] |= index << (8 - bits - bit % 8);
+        }
+    }
+    packed
+}
+
+fn set_color_space<W: std::io::Write>(
+    encoder: &mut png::Encoder<'_, W>,
+    config: &ExtractionConfig,
+) {
+    if !config.srgb {
+        return;
+    }
+    encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+    if config.srgb_fallback_chunks {
+        // The values the PNG spec prescribes alongside an sRGB chunk.
+        encoder.set_source_gamma(png::ScaledFloat::from_scaled(45455));
+        encoder.set_source_chromaticities(png::SourceChromaticities::new(
+            (0.3127, 0.3290),
+            (0.64, 0.33),
+            (0.30, 0.60),
+            (0.15, 0.06),
+        ));
+    }
+}
+
+/// Alpha-blends `img` over a solid `background`.
+fn flatten(img: &RgbaImage, background: Rgb<u8>) -> RgbImage {
+    RgbImage::from_fn(img.width(), img.height(), |x, y| {
+        let [r, g, b, a] = img.get_pixel(x, y).0;
+        let blend =
+            |fg: u8, bg: u8| ((fg as u32 * a as u32 + bg as u32 * (255 - a as u32)) / 255) as u8;
+        Rgb([
+            blend(r, background[0]),
+            blend(g, background[1]),
+            blend(b, background[2]),
+        ])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convert;
+    use image::{GenericImageView, Rgba};
+
+    /// Reads the header and ancillary chunks of an encoded PNG.
+    fn png_info(bytes: &[u8]) -> png::Reader<&[u8]> {
+        png::Decoder::new(bytes).read_info().unwrap()
+    }
+
+    #[test]
+    fn grayscale_png_is_gray_alpha() {
+        let img = RgbaImage::from_pixel(8, 8, Rgba([200, 100, 50, 128]));
+        let gray = DynamicImage::from(convert::to_grayscale(&img));
+        let bytes = OutputFormat::Png
+            .encode(&gray, &ExtractionConfig::default())
+            .unwrap();
+
+        let mut reader = png_info(&bytes);
+        assert_eq!(reader.info().color_type, png::ColorType::GrayscaleAlpha);
+        let mut data = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut data).unwrap();
+        assert_eq!(&data[..2], &[118, 128]);
+    }
+
+    #[test]
+    fn srgb_png_has_color_space_chunks() {
+        let img = DynamicImage::from(RgbaImage::from_pixel(4, 4, Rgba([1, 2, 3, 255])));
+        let mut config = ExtractionConfig::default();
+
+        let info = png_info(&OutputFormat::Png.encode(&img, &config).unwrap())
+            .info()
+            .clone();
+        assert_eq!(info.srgb, None);
+
+        config.srgb = true;
+        let info = png_info(&OutputFormat::Png.encode(&img, &config).unwrap())
+            .info()
+            .clone();
+        assert_eq!(info.srgb, Some(png::SrgbRenderingIntent::Perceptual));
+        assert_eq!(info.gama_chunk, None);
+        assert_eq!(info.chrm_chunk, None);
+
+        config.srgb_fallback_chunks = true;
+        let info = png_info(&OutputFormat::Png.encode(&img, &config).unwrap())
+            .info()
+            .clone();
+        assert_eq!(info.srgb, Some(png::SrgbRenderingIntent::Perceptual));
+        assert_eq!(info.gama_chunk, Some(png::ScaledFloat::from_scaled(45455)));
+        assert_eq!(
+            info.chrm_chunk,
+            Some(png::SourceChromaticities::new(
+                (0.3127, 0.3290),
+                (0.64, 0.33),
+                (0.30, 0.60),
+                (0.15, 0.06),
+            ))
+        );
+    }
+
+    #[test]
+    fn indexed_png_keeps_pixels() {
+        let img = RgbaImage::from_fn(5, 3, |x, y| match (x + y) % 3 {
+            0 => Rgba([9, 9, 9, 0]),
+            1 => Rgba([255, 0, 0, 255]),
+            _ => Rgba([0, 128, 255, 64]),
+        });
+        let config = ExtractionConfig {
+            indexed_png: true,
+            ..ExtractionConfig::default()
+        };
+        let bytes = OutputFormat::Png
+            .encode(&DynamicImage::from(img.clone()), &config)
+            .unwrap();
+
+        assert_eq!(png_info(&bytes).info().color_type, png::ColorType::Indexed);
+        let decoded = image::load_from_memory(&bytes).unwrap().to_rgba8();
+        for (pixel, original) in decoded.pixels().zip(img.pixels()) {
+            match original[3] {
+                0 => assert_eq!(pixel[3], 0),
+                _ => assert_eq!(pixel, original),
+            }
+        }
+    }
+
+    #[test]
+    fn color_quantize_picks_smallest_bit_depth() {
+        let img = DynamicImage::from(RgbaImage::from_fn(7, 5, |x, y| {
+            Rgba([x as u8 * 36, y as u8 * 50, 128, 255])
+        }));
+
+        for (palette_size, depth) in [
+            (2, png::BitDepth::One),
+            (4, png::BitDepth::Two),
+            (16, png::BitDepth::Four),
+            (200, png::BitDepth::Eight),
+        ] {
+            let config = ExtractionConfig::default().color_quantize(palette_size);
+            let bytes = OutputFormat::Png.encode(&img, &config).unwrap();
+
+            let mut reader = png_info(&bytes);
+            let info = reader.info();
+            assert_eq!(info.color_type, png::ColorType::Indexed);
+            assert_eq!(info.bit_depth, depth, "palette_size {palette_size}");
+            let palette_len = info.palette.as_ref().unwrap().len() / 3;
+            assert!(palette_len <= palette_size as usize);
+            // The packed rows must decode to exactly the image's size.
+            let mut data = vec![0; reader.output_buffer_size()];
+            let frame = reader.next_frame(&mut data).unwrap();
+            assert_eq!((frame.width, frame.height), (7, 5));
+        }
+    }
+
+    /// Half-transparent red, for checking what each format does with alpha.
+    fn translucent() -> DynamicImage {
+        RgbaImage::from_pixel(16, 16, Rgba([200, 0, 0, 128])).into()
+    }
+
+    #[test]
+    fn png_keeps_alpha() {
+        let bytes = OutputFormat::Png
+            .encode(&translucent(), &ExtractionConfig::default())
+            .unwrap();
+        let decoded = image::load_from_memory_with_format(&bytes, ImageFormat::Png).unwrap();
+        assert_eq!(decoded.to_rgba8().get_pixel(3, 3), &Rgba([200, 0, 0, 128]));
+    }
+
+    #[test]
+    fn ico_round_trips_alpha() {
+        let bytes = OutputFormat::Ico
+            .encode(&translucent(), &ExtractionConfig::default())
+            .unwrap();
+        assert_eq!(&bytes[..4], &[0, 0, 1, 0]);
+        let decoded = image::load_from_memory_with_format(&bytes, ImageFormat::Ico).unwrap();
+        assert_eq!(decoded.dimensions(), (16, 16));
+        assert_eq!(decoded.to_rgba8().get_pixel(3, 3), &Rgba([200, 0, 0, 128]));
+    }
+
+    #[test]
+    fn jpeg_is_flattened_on_white() {
+        let bytes = OutputFormat::Jpeg
+            .encode(&translucent(), &ExtractionConfig::default())
+            .unwrap();
+        let decoded = image::load_from_memory_with_format(&bytes, ImageFormat::Jpeg).unwrap();
+        assert!(!decoded.color().has_alpha());
+
+        // 200 * 128/255 + 255 * 127/255 for red, 255 * 127/255 for the rest;
+        // JPEG is lossy, so allow a little drift.
+        let [r, g, b] = decoded.to_rgb8().get_pixel(8, 8).0;
+        for (actual, expected) in [(r, 227), (g, 127), (b, 127)] {
+            assert!(actual.abs_diff(expected) <= 4, "{r} {g} {b}");
+        }
+    }
+}
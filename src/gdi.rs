@@ -0,0 +1,166 @@
+use crate::{IconError, IconWarning};
+use anyhow::Result;
+use image::{ImageBuffer, RgbaImage};
+use std::ptr::null_mut;
+use winapi::shared::windef::{HBITMAP, HICON};
+use winapi::um::wingdi::{BITMAP, BITMAPINFO, BITMAPINFOHEADER, DeleteObject, GetObjectW};
+use winapi::um::wingdi::{DIB_RGB_COLORS, GetDIBits};
+use winapi::um::winuser::{GetDC, GetIconInfo, ICONINFO, ReleaseDC};
+
+/// Largest edge accepted from an icon bitmap. Real icons top out at 256px;
+/// this leaves headroom while keeping a corrupt header from asking for
+/// gigabytes of pixels.
+const MAX_ICON_DIMENSION: i32 = 1024;
+
+fn check_dimensions(width: i32, height: i32) -> Result<()> {
+    let reasonable = |edge: i32| (1..=MAX_ICON_DIMENSION).contains(&edge);
+    if !reasonable(width) || !reasonable(height) {
+        return Err(IconError::UnreasonableDimensions { width, height }.into());
+    }
+    Ok(())
+}
+
+/// Renders `hicon` into an RGBA buffer. The caller keeps ownership of the icon.
+pub(crate) unsafe fn hicon_to_image(hicon: HICON) -> Result<RgbaImage> {
+    unsafe { hicon_to_image_with_warnings(hicon).map(|(img, _)| img) }
+}
+
+/// Like `hicon_to_image`, also reporting the quality caveats of the
+/// conversion.
+pub(crate) unsafe fn hicon_to_image_with_warnings(
+    hicon: HICON,
+) -> Result<(RgbaImage, Vec<IconWarning>)> {
+    unsafe {
+        let mut icon_info = std::mem::zeroed();
+        if GetIconInfo(hicon, &mut icon_info) == 0 {
+            anyhow::bail!("GetIconInfo failed.");
+        }
+
+        let img = bitmap_to_image(&icon_info);
+
+        DeleteObject(icon_info.hbmColor as _);
+        DeleteObject(icon_info.hbmMask as _);
+
+        img
+    }
+}
+
+unsafe fn bitmap_to_image(icon_info: &ICONINFO) -> Result<(RgbaImage, Vec<IconWarning>)> {
+    unsafe {
+        let mut bmp: BITMAP = std::mem::zeroed();
+        if GetObjectW(
+            icon_info.hbmColor as _,
+            std::mem::size_of::<BITMAP>() as i32,
+            &mut bmp as *mut _ as _,
+        ) == 0
+        {
+            anyhow::bail!("GetObjectW failed.");
+        }
+        check_dimensions(bmp.bmWidth, bmp.bmHeight)?;
+        let width = bmp.bmWidth as usize;
+        let height = bmp.bmHeight as usize;
+
+        let mut warnings = Vec::new();
+        if bmp.bmBitsPixel < 32 {
+            warnings.push(IconWarning::LowColorDepth {
+                bits: bmp.bmBitsPixel,
+            });
+        }
+
+        let mut pixels = dib_pixels(icon_info.hbmColor, width, height)?;
+
+        // GDI hands out BGRA
+        for px in pixels.chunks_exact_mut(4) {
+            px.swap(0, 2);
+        }
+
+        // Icons without an alpha channel keep their transparency in the AND
+        // mask, where a set bit (white) means transparent.
+        if pixels.chunks_exact(4).all(|px| px[3] == 0) {
+            let mask = dib_pixels(icon_info.hbmMask, width, height)?;
+            for (px, mask) in pixels.chunks_exact_mut(4).zip(mask.chunks_exact(4)) {
+                px[3] = if mask[0] == 0 { 255 } else { 0 };
+            }
+            warnings.push(IconWarning::AlphaFromMask);
+        }
+
+        let img = ImageBuffer::from_raw(width as u32, height as u32, pixels)
+            .ok_or_else(|| anyhow::anyhow!("Failed to create ImageBuffer"))?;
+        Ok((img, warnings))
+    }
+}
+
+/// Reads `bitmap` as 32bpp BGRA rows.
+unsafe fn dib_pixels(bitmap: HBITMAP, width: usize, height: usize) -> Result<Vec<u8>> {
+    unsafe {
+        let mut bmp_info = BITMAPINFO {
+            bmiHeader: BITMAPINFOHEADER {
+                biSize: std::mem::size_of::<BITMAPINFOHEADER>() as u32,
+                biWidth: width as i32,
+                biHeight: -(height as i32), // 负表示自顶向下
+                biPlanes: 1,
+                biBitCount: 32,
+                biCompression: 0, // BI_RGB
+                biSizeImage: 0,
+                biXPelsPerMeter: 0,
+                biYPelsPerMeter: 0,
+                biClrUsed: 0,
+                biClrImportant: 0,
+            },
+            bmiColors: [std::mem::zeroed(); 1],
+        };
+
+        let mut pixels = vec![0u8; width * height * 4];
+
+        let hdc = GetDC(null_mut());
+        let ret = GetDIBits(
+            hdc,
+            bitmap,
+            0,
+            height as u32,
+            pixels.as_mut_ptr() as _,
+            &mut bmp_info,
+            DIB_RGB_COLORS,
+        );
+        ReleaseDC(null_mut(), hdc);
+
+        if ret == 0 {
+            anyhow::bail!("GetDIBits failed.");
+        }
+
+        Ok(pixels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rejects(width: i32, height: i32) -> bool {
+        check_dimensions(width, height).is_err_and(|err| {
+            err.downcast_ref() == Some(&IconError::UnreasonableDimensions { width, height })
+        })
+    }
+
+    #[test]
+    fn accepts_icon_sized_bitmaps() {
+        for (width, height) in [(1, 1), (32, 32), (256, 256), (1024, 1024), (16, 1024)] {
+            assert!(check_dimensions(width, height).is_ok(), "{width}x{height}");
+        }
+    }
+
+    #[test]
+    fn rejects_absurd_dimensions() {
+        for (width, height) in [
+            (0, 32),
+            (32, 0),
+            (-32, 32),
+            (32, -1),
+            (1025, 32),
+            (32, 1025),
+        ] {
+            assert!(rejects(width, height), "{width}x{height}");
+        }
+        assert!(rejects(i32::MAX, i32::MIN));
+    }
+}
@@ -0,0 +1,240 @@
+use crate::IconError;
+use crate::icon_group::{IconImage, build_ico, parse_ico};
+use anyhow::{Context, Result};
+use image::{ImageFormat, ImageReader, Limits, RgbaImage};
+use std::collections::HashSet;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::slice;
+
+/// Largest width or height accepted for an image in an ICO file. Classic
+/// entries top out at 256; PNG entries can be larger, but not by much in
+/// practice.
+const MAX_IMAGE_DIMENSION: u32 = 1024;
+/// Most memory the decoder may allocate for a single image.
+const MAX_IMAGE_ALLOC: u64 = 64 * 1024 * 1024;
+
+/// Writes every image stored in an `.ico` file to `out_dir` as
+/// `icon_<w>x<h>.png`. BMP and PNG encoded entries are both supported; when
+/// a size appears more than once the later copies get a `_<n>` suffix.
+pub fn split_ico(ico_path: &Path, out_dir: &Path) -> Result<Vec<PathBuf>> {
+    let bytes = std::fs::read(ico_path)
+        .with_context(|| format!("Failed to open icon file: {}", ico_path.display()))?;
+    let images = decode_ico(&bytes)
+        .with_context(|| format!("Failed to parse icon file: {}", ico_path.display()))?;
+
+    let mut names = HashSet::new();
+    let mut written = Vec::new();
+
+    for img in images {
+        let base = format!("icon_{}x{}", img.width(), img.height());
+        let mut name = base.clone();
+        for n in 2.. {
+            if names.insert(name.clone()) {
+                break;
+            }
+            name = format!("{base}_{n}");
+        }
+
+        let output_path = out_dir.join(format!("{name}.png"));
+        img.save(&output_path)?;
+        written.push(output_path);
+    }
+
+    Ok(written)
+}
+
+/// Decodes every image of an ICO file. The input is treated as untrusted:
+/// malformed files fail with [`IconError::MalformedIco`], and decoding is
+/// capped in size and memory.
+pub fn decode_ico(bytes: &[u8]) -> Result<Vec<RgbaImage>> {
+    parse_ico(bytes)?
+        .iter()
+        .enumerate()
+        .map(|(i, image)| {
+            decode_image(image)
+                .map_err(|e| IconError::MalformedIco(format!("image {i}: {e}")).into())
+        })
+        .collect()
+}
+
+/// Decodes one BMP or PNG icon image within the size and memory caps.
+pub(crate) fn decode_image(image: &IconImage) -> image::ImageResult<RgbaImage> {
+    // Each image is decoded on its own so the limits apply per image.
+    let single = build_ico(slice::from_ref(image));
+    let mut reader = ImageReader::with_format(Cursor::new(single), ImageFormat::Ico);
+    reader.limits(untrusted_limits());
+
+    Ok(reader.decode()?.to_rgba8())
+}
+
+/// Decoder limits for image data from files we don't trust.
+pub(crate) fn untrusted_limits() -> Limits {
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_IMAGE_DIMENSION);
+    limits.max_image_height = Some(MAX_IMAGE_DIMENSION);
+    limits.max_alloc = Some(MAX_IMAGE_ALLOC);
+    limits
+}
+
+#[cfg(test)]
+pub(crate) mod fixture {
+    use image::{ImageFormat, RgbaImage};
+    use std::io::Cursor;
+
+    /// A `BITMAPINFOHEADER` for a `size`x`size` icon image at `bits` per
+    /// pixel; the height is doubled to cover the AND mask.
+    fn bitmap_header(size: u32, bits: u16) -> Vec<u8> {
+        let mut header = Vec::new();
+        header.extend_from_slice(&40u32.to_le_bytes());
+        header.extend_from_slice(&(size as i32).to_le_bytes());
+        header.extend_from_slice(&(2 * size as i32).to_le_bytes());
+        header.extend_from_slice(&1u16.to_le_bytes());
+        header.extend_from_slice(&bits.to_le_bytes());
+        header.extend_from_slice(&[0; 24]);
+        header
+    }
+
+    /// Appends an all-zero (fully opaque) AND mask for a `size`x`size` image.
+    fn push_and_mask(bmp: &mut Vec<u8>, size: u32) {
+        let mask_row = (size as usize).div_ceil(32) * 4;
+        bmp.resize(bmp.len() + mask_row * size as usize, 0);
+    }
+
+    /// A 32bpp BMP icon image (BITMAPINFOHEADER, pixels and AND mask),
+    /// filled with one color at `alpha`.
+    pub(crate) fn bmp_image(size: u32, alpha: u8) -> Vec<u8> {
+        let mut bmp = bitmap_header(size, 32);
+        for _ in 0..size * size {
+            bmp.extend_from_slice(&[0x30, 0x20, 0x10, alpha]);
+        }
+        push_and_mask(&mut bmp, size);
+        bmp
+    }
+
+    /// A monochrome BMP icon image: black and white palette, an all-white
+    /// XOR mask and the AND mask.
+    pub(crate) fn mono_bmp_image(size: u32) -> Vec<u8> {
+        let mut bmp = bitmap_header(size, 1);
+        bmp.extend_from_slice(&[0, 0, 0, 0, 0xff, 0xff, 0xff, 0]);
+        let row = (size as usize).div_ceil(32) * 4;
+        bmp.resize(bmp.len() + row * size as usize, 0xff);
+        push_and_mask(&mut bmp, size);
+        bmp
+    }
+
+    pub(crate) fn png_image(size: u32) -> Vec<u8> {
+        let mut png = Vec::new();
+        RgbaImage::from_pixel(size, size, image::Rgba([1, 2, 3, 255]))
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        png
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fixture::*;
+    use super::*;
+
+    /// A one-image ICO header and directory entry for `size` bytes of image
+    /// data at `offset`.
+    fn ico_with_entry(size: u32, offset: u32) -> Vec<u8> {
+        let mut ico = vec![0, 0, 1, 0, 1, 0];
+        ico.extend_from_slice(&[0, 0, 0, 0, 1, 0, 32, 0]);
+        ico.extend_from_slice(&size.to_le_bytes());
+        ico.extend_from_slice(&offset.to_le_bytes());
+        ico
+    }
+
+    fn assert_malformed(bytes: &[u8]) {
+        let err = decode_ico(bytes).unwrap_err();
+        assert!(
+            matches!(err.downcast_ref(), Some(IconError::MalformedIco(_))),
+            "unexpected error: {err:#}"
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_header() {
+        assert_malformed(&[0, 0, 1]);
+    }
+
+    #[test]
+    fn rejects_bad_magic() {
+        assert_malformed(&[0, 0, 2, 0, 1, 0]);
+    }
+
+    #[test]
+    fn rejects_zero_images() {
+        assert_malformed(&[0, 0, 1, 0, 0, 0]);
+    }
+
+    #[test]
+    fn rejects_truncated_entries() {
+        let mut ico = ico_with_entry(40, 22);
+        ico.truncate(14);
+        assert_malformed(&ico);
+    }
+
+    #[test]
+    fn rejects_image_past_end_of_file() {
+        assert_malformed(&ico_with_entry(100, 22));
+    }
+
+    #[test]
+    fn rejects_image_over_size_limit() {
+        // A BITMAPINFOHEADER claiming 8192x8192 (doubled height for the mask).
+        let mut ico = ico_with_entry(40, 22);
+        ico.extend_from_slice(&40u32.to_le_bytes());
+        ico.extend_from_slice(&8192i32.to_le_bytes());
+        ico.extend_from_slice(&16384i32.to_le_bytes());
+        ico.extend_from_slice(&1u16.to_le_bytes());
+        ico.extend_from_slice(&32u16.to_le_bytes());
+        ico.extend_from_slice(&[0; 24]);
+        assert_malformed(&ico);
+        let err = decode_ico(&ico).unwrap_err().to_string();
+        assert!(err.contains("limit"), "{err}");
+    }
+
+    #[test]
+    fn split_ico_writes_one_png_per_entry() {
+        let entry = |size: u32, bits: u8| [size as u8, size as u8, 0, 0, 1, 0, bits, 0];
+        let (bmp16, png32, png32_again) = (bmp_image(16, 0xff), png_image(32), png_image(32));
+        let (fields16, fields32) = (entry(16, 32), entry(32, 32));
+        let ico = build_ico(&[
+            IconImage {
+                fields: &fields16,
+                data: &bmp16,
+            },
+            IconImage {
+                fields: &fields32,
+                data: &png32,
+            },
+            IconImage {
+                fields: &fields32,
+                data: &png32_again,
+            },
+        ]);
+        let dir = tempfile::tempdir().unwrap();
+        let ico_path = dir.path().join("app.ico");
+        std::fs::write(&ico_path, ico).unwrap();
+
+        let written = split_ico(&ico_path, dir.path()).unwrap();
+
+        let names: Vec<_> = written
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(
+            names,
+            ["icon_16x16.png", "icon_32x32.png", "icon_32x32_2.png"]
+        );
+        for (path, size) in written.iter().zip([16, 32, 32]) {
+            let img = image::open(path).unwrap();
+            assert_eq!((img.width(), img.height()), (size, size));
+        }
+        let bmp = image::open(&written[0]).unwrap().to_rgba8();
+        assert_eq!(bmp.get_pixel(0, 0), &image::Rgba([0x10, 0x20, 0x30, 0xff]));
+    }
+}
@@ -0,0 +1,337 @@
+use crate::pe::{ResourceKey, Resources};
+use crate::{IconError, check_icon_file, ordinal};
+use anyhow::{Context, Result};
+use object::pe::{RT_GROUP_ICON, RT_ICON};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Size of the `ICONDIR`/`GRPICONDIR` header shared by ICO files and icon
+/// group resources.
+const DIR_HEADER_SIZE: usize = 6;
+/// `GRPICONDIRENTRY`: the ICO entry fields, with a 2-byte `RT_ICON` ID in
+/// place of the 4-byte file offset.
+const GROUP_ENTRY_SIZE: usize = 14;
+const ICO_ENTRY_SIZE: usize = 16;
+
+/// Writes each `RT_GROUP_ICON` resource of the file as `icon_<resource_id>.ico`
+/// in `output_dir`, with every size variant of the group. Unlike the other
+/// extractors this copies the original images byte for byte, so the result
+/// can be re-embedded elsewhere without loss.
+pub fn extract_all_icon_groups_as_icos(
+    file_path: &Path,
+    output_dir: &Path,
+) -> Result<Vec<PathBuf>> {
+    let data = std::fs::read(check_icon_file(file_path)?)?;
+    let Some(resources) = Resources::parse_file(file_path, &data)? else {
+        return Ok(Vec::new());
+    };
+
+    let icons = icon_images(&resources)?;
+    let mut written = Vec::new();
+    for (index, group) in resources.of_type(RT_GROUP_ICON)?.into_iter().enumerate() {
+        let key = group.key;
+        let ico = assemble_ico(group.data, &icons)
+            .with_context(|| format!("Malformed icon group {key} in {}", file_path.display()))?;
+
+        let output_path = output_dir.join(ico_file_name(&key, index));
+        std::fs::write(&output_path, ico)?;
+        written.push(output_path);
+    }
+
+    Ok(written)
+}
+
+/// `icon_<key>.ico` for the group at `index`. Names come from the file and
+/// may be hostile, so anything that could leave `output_dir` or address an
+/// NTFS stream is replaced, falling back to `icon_group<index>.ico` when
+/// nothing usable is left.
+fn ico_file_name(key: &ResourceKey, index: usize) -> String {
+    let name = match key {
+        ResourceKey::Id(id) => return format!("icon_{id}.ico"),
+        ResourceKey::Name(name) => name,
+    };
+    let mut safe: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    while safe.contains("..") {
+        safe = safe.replace("..", "_");
+    }
+    let safe = safe.trim_matches(|c| c == '.' || c == ' ' || c == '_');
+    if safe.is_empty() {
+        format!("icon_group{index}.ico")
+    } else {
+        format!("icon_{safe}.ico")
+    }
+}
+
+/// The square sizes the icon group at `index` holds images for, smallest
+/// first.
+pub(crate) fn native_sizes(file_path: &Path, index: u32) -> Result<Vec<u32>> {
+    let data = std::fs::read(check_icon_file(file_path)?)?;
+    let groups = match Resources::parse_file(file_path, &data)? {
+        Some(resources) => resources.of_type(RT_GROUP_ICON)?,
+        None => Vec::new(),
+    };
+    let Some(group) = groups.get(index as usize) else {
+        return Err(IconError::NoIcon {
+            path: file_path.to_path_buf(),
+            index: ordinal(index)?,
+            count: Some(groups.len() as u32),
+        }
+        .into());
+    };
+
+    // A width or height of 0 stands for 256.
+    let dimension = |byte: u8| if byte == 0 { 256 } else { byte as u32 };
+    let mut sizes: Vec<u32> = parse_group(group.data)?
+        .into_iter()
+        .filter(|(fields, _)| fields[0] == fields[1])
+        .map(|(fields, _)| dimension(fields[0]))
+        .collect();
+    sizes.sort_unstable();
+    sizes.dedup();
+    Ok(sizes)
+}
+
+/// The file's `RT_ICON` images by ID, which is how groups refer to them.
+fn icon_images<'data>(resources: &Resources<'data>) -> Result<HashMap<u16, &'data [u8]>> {
+    Ok(resources
+        .of_type(RT_ICON)?
+        .into_iter()
+        .filter_map(|icon| match icon.key {
+            ResourceKey::Id(id) => Some((id, icon.data)),
+            ResourceKey::Name(_) => None,
+        })
+        .collect())
+}
+
+/// One image of an ICO file or icon group. `fields` are the leading entry
+/// fields both formats share: width, height, colors, reserved, planes and
+/// bit count.
+pub(crate) struct IconImage<'a> {
+    pub fields: &'a [u8],
+    pub data: &'a [u8],
+}
+
+/// Number of `fields` bytes in an `IconImage`.
+const SHARED_FIELDS_SIZE: usize = 8;
+
+/// Reads the entries of a `GRPICONDIR` as their shared fields and the ID of
+/// the `RT_ICON` holding each image.
+pub(crate) fn parse_group(group: &[u8]) -> Result<Vec<(&[u8], u16)>> {
+    let count = directory_count(group).context("Icon group header is truncated.")?;
+    let entries = group
+        .get(DIR_HEADER_SIZE..DIR_HEADER_SIZE + count * GROUP_ENTRY_SIZE)
+        .context("Icon group entries are truncated.")?;
+
+    Ok(entries
+        .chunks_exact(GROUP_ENTRY_SIZE)
+        .map(|entry| {
+            let id = u16::from_le_bytes([entry[12], entry[13]]);
+            (&entry[..SHARED_FIELDS_SIZE], id)
+        })
+        .collect())
+}
+
+/// Builds a `GRPICONDIR` for `images`, stored as the `RT_ICON`s in `ids`.
+pub(crate) fn build_group(images: &[IconImage], ids: &[u16]) -> Vec<u8> {
+    let mut group = directory_header(images.len());
+    for (image, id) in images.iter().zip(ids) {
+        group.extend_from_slice(image.fields);
+        group.extend_from_slice(&(image.data.len() as u32).to_le_bytes());
+        group.extend_from_slice(&id.to_le_bytes());
+    }
+    group
+}
+
+/// Splits an ICO file into its images. Counts, offsets and sizes are all
+/// checked against the input, so corrupt or hostile files fail with
+/// [`IconError::MalformedIco`] instead of panicking or allocating.
+pub(crate) fn parse_ico(ico: &[u8]) -> Result<Vec<IconImage<'_>>> {
+    let malformed = |reason: &str| IconError::MalformedIco(reason.to_string());
+
+    let count = directory_count(ico).ok_or_else(|| malformed("header is truncated"))?;
+    if ico[..4] != [0, 0, 1, 0] {
+        return Err(malformed("not an icon file").into());
+    }
+    if count == 0 {
+        return Err(malformed("no images").into());
+    }
+    let entries = ico
+        .get(DIR_HEADER_SIZE..DIR_HEADER_SIZE + count * ICO_ENTRY_SIZE)
+        .ok_or_else(|| malformed("directory entries are truncated"))?;
+
+    entries
+        .chunks_exact(ICO_ENTRY_SIZE)
+        .map(|entry| {
+            let size = u32::from_le_bytes([entry[8], entry[9], entry[10], entry[11]]) as usize;
+            let offset = u32::from_le_bytes([entry[12], entry[13], entry[14], entry[15]]) as usize;
+            if size == 0 {
+                return Err(malformed("empty image").into());
+            }
+            let data = offset
+                .checked_add(size)
+                .and_then(|end| ico.get(offset..end))
+                .ok_or_else(|| malformed("image data lies outside the file"))?;
+            Ok(IconImage {
+                fields: &entry[..SHARED_FIELDS_SIZE],
+                data,
+            })
+        })
+        .collect()
+}
+
+/// Reads the images of the icon group at `index` out of `data`, the contents
+/// of PE file `file_path`, or returns `None` if there is no such group.
+pub(crate) fn group_images_at<'data>(
+    file_path: &Path,
+    data: &'data [u8],
+    index: u32,
+) -> Result<Option<Vec<IconImage<'data>>>> {
+    let Some(resources) = Resources::parse_file(file_path, data)? else {
+        return Ok(None);
+    };
+    let groups = resources.of_type(RT_GROUP_ICON)?;
+    let Some(group) = groups.get(index as usize) else {
+        return Ok(None);
+    };
+    group_images(group.data, &icon_images(&resources)?).map(Some)
+}
+
+/// Pairs the entries of a `GRPICONDIR` with the images they reference.
+/// Entries pointing at missing images are dropped, as Windows does.
+fn group_images<'a>(group: &'a [u8], icons: &HashMap<u16, &'a [u8]>) -> Result<Vec<IconImage<'a>>> {
+    let images: Vec<IconImage> = parse_group(group)?
+        .into_iter()
+        .filter_map(|(fields, id)| icons.get(&id).map(|&data| IconImage { fields, data }))
+        .collect();
+    if images.is_empty() {
+        anyhow::bail!("Icon group references no existing images.");
+    }
+    Ok(images)
+}
+
+/// Builds an ICO file from a `GRPICONDIR` and the images it references.
+fn assemble_ico(group: &[u8], icons: &HashMap<u16, &[u8]>) -> Result<Vec<u8>> {
+    Ok(build_ico(&group_images(group, icons)?))
+}
+
+/// Lays `images` out as an ICO file.
+pub(crate) fn build_ico(images: &[IconImage]) -> Vec<u8> {
+    let mut ico = directory_header(images.len());
+    let mut offset = DIR_HEADER_SIZE + images.len() * ICO_ENTRY_SIZE;
+    for image in images {
+        // Size and offset describe where the image sits in this file.
+        ico.extend_from_slice(image.fields);
+        ico.extend_from_slice(&(image.data.len() as u32).to_le_bytes());
+        ico.extend_from_slice(&(offset as u32).to_le_bytes());
+        offset += image.data.len();
+    }
+    for image in images {
+        ico.extend_from_slice(image.data);
+    }
+    ico
+}
+
+fn directory_count(dir: &[u8]) -> Option<usize> {
+    let header = dir.get(..DIR_HEADER_SIZE)?;
+    Some(u16::from_le_bytes([header[4], header[5]]) as usize)
+}
+
+fn directory_header(count: usize) -> Vec<u8> {
+    let mut header = vec![0, 0, 1, 0];
+    header.extend_from_slice(&(count as u16).to_le_bytes());
+    header
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pe::fixture::{build_pe, build_pe_with_keys, write_temp};
+
+    #[test]
+    fn hostile_group_names_stay_inside_output_dir() {
+        let fields = [16, 16, 0, 0, 1, 0, 32, 0];
+        let group = build_group(
+            &[IconImage {
+                fields: &fields,
+                data: b"image",
+            }],
+            &[1],
+        );
+        let name = |name: &str| ResourceKey::Name(name.to_string());
+        let pe = build_pe_with_keys(&[
+            (RT_ICON, ResourceKey::Id(1), b"image"),
+            (RT_GROUP_ICON, name(r"\..\..\evil"), &group),
+            (RT_GROUP_ICON, name("a:stream"), &group),
+            (RT_GROUP_ICON, name("../.."), &group),
+            (RT_GROUP_ICON, name("MAINICON"), &group),
+            (RT_GROUP_ICON, ResourceKey::Id(7), &group),
+        ]);
+        let (dir, path) = write_temp("app.exe", &pe);
+        let out_dir = dir.path().join("out");
+        std::fs::create_dir(&out_dir).unwrap();
+
+        let written = extract_all_icon_groups_as_icos(&path, &out_dir).unwrap();
+
+        let names: Vec<_> = written
+            .iter()
+            .map(|path| {
+                assert_eq!(path.parent(), Some(out_dir.as_path()));
+                path.file_name().unwrap().to_string_lossy().into_owned()
+            })
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "icon_evil.ico",
+                "icon_a_stream.ico",
+                "icon_group2.ico",
+                "icon_MAINICON.ico",
+                "icon_7.ico",
+            ]
+        );
+        assert_eq!(std::fs::read_dir(&out_dir).unwrap().count(), 5);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn native_sizes_lists_square_sizes_once_smallest_first() {
+        let entry = |size: u8, bits: u8| [size, size, 0, 0, 1, 0, bits, 0];
+        let fields = [
+            entry(48, 32),
+            entry(0, 32),
+            entry(16, 32),
+            entry(16, 8),
+            [32, 16, 0, 0, 1, 0, 32, 0],
+        ];
+        let images: Vec<_> = fields
+            .iter()
+            .map(|fields| IconImage {
+                fields,
+                data: b"image",
+            })
+            .collect();
+        let group = build_group(&images, &[1, 2, 3, 4, 5]);
+        let (_dir, path) = write_temp("app.exe", &build_pe(&[(RT_GROUP_ICON, 1, &group)]));
+
+        // 0 stands for 256, the 32x16 entry isn't square, and 16 appears at
+        // two bit depths.
+        assert_eq!(native_sizes(&path, 0).unwrap(), [16, 48, 256]);
+
+        let err = native_sizes(&path, 1).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(IconError::NoIcon {
+                index: 1,
+                count: Some(1),
+                ..
+            })
+        ));
+    }
+}
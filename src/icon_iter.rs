@@ -0,0 +1,179 @@
+use crate::check_icon_file;
+use crate::ico_file::decode_image;
+use crate::icon_group::{IconImage, parse_group};
+use crate::pe::{Resource, ResourceKey, Resources};
+use anyhow::{Context, Result};
+use image::DynamicImage;
+use object::pe::{RT_GROUP_ICON, RT_ICON};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::Path;
+
+/// Iterates over the icon groups of a PE file, decoding one icon per step.
+///
+/// The resource directory is read once by [`IconIterator::new`]; each call to
+/// `next` only decodes the largest image of the next group, so large icon
+/// libraries like `shell32.dll` never need to be held decoded all at once.
+pub struct IconIterator {
+    data: Vec<u8>,
+    /// Where each `RT_GROUP_ICON` lies in `data`, in resource order.
+    groups: Vec<Range<usize>>,
+    /// Where each `RT_ICON` lies in `data`, by ID.
+    icons: HashMap<u16, Range<usize>>,
+    next: usize,
+}
+
+impl IconIterator {
+    /// Reads the file and its resource directory, without decoding anything.
+    pub fn new(file_path: &Path) -> Result<IconIterator> {
+        let data = std::fs::read(check_icon_file(file_path)?)?;
+        let (groups, icons) = match Resources::parse_file(file_path, &data)? {
+            Some(resources) => {
+                let range = |resource: &Resource| {
+                    let offset = resources
+                        .file_offset(resource.rva)
+                        .context("Resource data lies outside the file.")?
+                        as usize;
+                    Ok::<_, anyhow::Error>(offset..offset + resource.data.len())
+                };
+                let groups = resources
+                    .of_type(RT_GROUP_ICON)?
+                    .iter()
+                    .map(range)
+                    .collect::<Result<_>>()?;
+                let mut icons = HashMap::new();
+                for icon in resources.of_type(RT_ICON)? {
+                    if let ResourceKey::Id(id) = icon.key {
+                        icons.insert(id, range(&icon)?);
+                    }
+                }
+                (groups, icons)
+            }
+            None => (Vec::new(), HashMap::new()),
+        };
+
+        Ok(IconIterator {
+            data,
+            groups,
+            icons,
+            next: 0,
+        })
+    }
+
+    fn decode(&self, index: usize) -> Result<DynamicImage> {
+        let group = &self.data[self.groups[index].clone()];
+        let images: Vec<IconImage> = parse_group(group)?
+            .into_iter()
+            .filter_map(|(fields, id)| {
+                let range = self.icons.get(&id)?.clone();
+                Some(IconImage {
+                    fields,
+                    data: &self.data[range],
+                })
+            })
+            .collect();
+
+        // A width of 0 stands for 256; prefer the deepest image of the largest size.
+        let best = images
+            .iter()
+            .max_by_key(|image| {
+                let width = if image.fields[0] == 0 {
+                    256
+                } else {
+                    image.fields[0] as u32
+                };
+                let bit_count = u16::from_le_bytes([image.fields[6], image.fields[7]]);
+                (width, bit_count)
+            })
+            .with_context(|| format!("Icon group {index} references no existing images."))?;
+
+        decode_image(best)
+            .map(DynamicImage::ImageRgba8)
+            .with_context(|| format!("Failed to decode icon group {index}"))
+    }
+}
+
+impl Iterator for IconIterator {
+    type Item = Result<DynamicImage>;
+
+    fn next(&mut self) -> Option<Result<DynamicImage>> {
+        if self.next >= self.groups.len() {
+            return None;
+        }
+        let img = self.decode(self.next);
+        self.next += 1;
+        Some(img)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.groups.len() - self.next;
+        (remaining, Some(remaining))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ico_file::fixture::{bmp_image, mono_bmp_image, png_image};
+    use crate::icon_group::build_group;
+    use crate::pe::fixture::{build_pe, write_temp};
+    use image::GenericImageView;
+
+    #[test]
+    fn decodes_the_best_image_of_each_group_in_turn() {
+        let entry = |size: u8, bits: u8| [size, size, 0, 0, 1, 0, bits, 0];
+        let (bmp16, mono32, png32) = (bmp_image(16, 0xff), mono_bmp_image(32), png_image(32));
+        let (bmp48, png256) = (bmp_image(48, 0xff), png_image(256));
+        let fields = [
+            entry(16, 32),
+            entry(32, 1),
+            entry(32, 32),
+            entry(48, 32),
+            entry(0, 32),
+        ];
+        let image = |fields, data| IconImage { fields, data };
+
+        let mixed = build_group(
+            &[
+                image(&fields[0], &bmp16),
+                image(&fields[1], &mono32),
+                image(&fields[2], &png32),
+            ],
+            &[1, 2, 3],
+        );
+        let missing = build_group(&[image(&fields[0], &bmp16)], &[99]);
+        let large = build_group(
+            &[image(&fields[3], &bmp48), image(&fields[4], &png256)],
+            &[4, 5],
+        );
+        let pe = build_pe(&[
+            (RT_ICON, 1, &bmp16),
+            (RT_ICON, 2, &mono32),
+            (RT_ICON, 3, &png32),
+            (RT_ICON, 4, &bmp48),
+            (RT_ICON, 5, &png256),
+            (RT_GROUP_ICON, 1, &mixed),
+            (RT_GROUP_ICON, 2, &missing),
+            (RT_GROUP_ICON, 3, &large),
+        ]);
+        let (_dir, path) = write_temp("app.exe", &pe);
+
+        let mut icons = IconIterator::new(&path).unwrap();
+        assert_eq!(icons.size_hint(), (3, Some(3)));
+
+        // Of the two 32px images the 32bpp PNG wins over the monochrome one.
+        let first = icons.next().unwrap().unwrap();
+        assert_eq!(first.dimensions(), (32, 32));
+        assert_eq!(first.get_pixel(0, 0), image::Rgba([1, 2, 3, 255]));
+        assert_eq!(icons.size_hint(), (2, Some(2)));
+
+        let err = icons.next().unwrap().unwrap_err();
+        assert!(err.to_string().contains("references no existing images"));
+        assert_eq!(icons.size_hint(), (1, Some(1)));
+
+        // A width byte of 0 means 256, the largest size here.
+        assert_eq!(icons.next().unwrap().unwrap().dimensions(), (256, 256));
+        assert_eq!(icons.size_hint(), (0, Some(0)));
+        assert!(icons.next().is_none());
+    }
+}
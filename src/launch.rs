@@ -0,0 +1,55 @@
+use crate::extract_icon_image;
+use anyhow::Result;
+use std::path::Path;
+#[cfg(windows)]
+use std::ptr::null_mut;
+#[cfg(windows)]
+use widestring::U16CString;
+#[cfg(windows)]
+use winapi::um::shellapi::ShellExecuteW;
+#[cfg(windows)]
+use winapi::um::winuser::SW_SHOWNORMAL;
+
+/// Extracts the icon at `index` to a temporary PNG and opens it with the
+/// default viewer. The file is left in place so the viewer can read it.
+pub fn extract_icon_and_open(file_path: &Path, index: u32) -> Result<()> {
+    let img = extract_icon_image(file_path, index)?;
+
+    let mut temp_dir = tempfile::tempdir()?;
+    temp_dir.disable_cleanup(true);
+    let icon_path = temp_dir.path().join("icon.png");
+    img.save(&icon_path)?;
+
+    open_with_shell(&icon_path)
+}
+
+/// Opens `path` with its associated application via `ShellExecuteW`.
+#[cfg(windows)]
+pub fn open_with_shell(path: &Path) -> Result<()> {
+    let verb = U16CString::from_str("open")?;
+    let file = U16CString::from_os_str(path.as_os_str())?;
+
+    let result = unsafe {
+        ShellExecuteW(
+            null_mut(),
+            verb.as_ptr(),
+            file.as_ptr(),
+            null_mut(),
+            null_mut(),
+            SW_SHOWNORMAL,
+        )
+    };
+    // Values above 32 mean success; anything else is an SE_ERR_* code.
+    if result as usize <= 32 {
+        anyhow::bail!("ShellExecuteW failed with code {}", result as usize);
+    }
+
+    Ok(())
+}
+
+/// Opens `path` with its default application.
+/// Always fails with [`crate::IconError::UnsupportedPlatform`] off Windows.
+#[cfg(not(windows))]
+pub fn open_with_shell(_path: &Path) -> Result<()> {
+    Err(crate::IconError::UnsupportedPlatform.into())
+}
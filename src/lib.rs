@@ -0,0 +1,612 @@
+//! Icon extraction is built on Win32 and only works on Windows. The crate
+//! still builds elsewhere so the platform-independent parts (conversion,
+//! encoding, parsing) can be used and tested; the Win32-backed functions
+//! return [`IconError::UnsupportedPlatform`] there.
+
+#![deny(missing_docs)]
+
+use anyhow::{Context, Result};
+use image::{DynamicImage, RgbaImage};
+use std::io::{self, Read, Write};
+#[cfg(windows)]
+use std::os::windows::ffi::OsStrExt;
+use std::path::Path;
+use std::path::PathBuf;
+#[cfg(windows)]
+use std::ptr::null_mut;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+use tempfile::NamedTempFile;
+#[cfg(windows)]
+use winapi::shared::minwindef::MAX_PATH;
+#[cfg(windows)]
+use winapi::shared::windef::HICON;
+#[cfg(windows)]
+use winapi::shared::winerror::*;
+#[cfg(windows)]
+use winapi::um::errhandlingapi::{GetLastError, SetLastError};
+#[cfg(windows)]
+use winapi::um::shellapi::ExtractIconExW;
+#[cfg(windows)]
+use winapi::um::winuser::DestroyIcon;
+
+mod archive;
+mod association;
+mod background;
+mod batch;
+mod cache;
+mod config;
+pub mod convert;
+#[cfg(feature = "disk-cache")]
+mod disk_cache;
+mod embed;
+mod error;
+mod fallback;
+mod favicon;
+mod format;
+#[cfg(windows)]
+mod gdi;
+mod ico_file;
+mod icon_group;
+mod icon_iter;
+mod launch;
+mod location;
+mod macos;
+mod metadata;
+mod palette;
+mod pe;
+mod scan;
+mod service;
+mod size;
+mod stock;
+mod update;
+mod verify;
+
+pub use archive::{extract_icon_from_zip, unpack_zip_entry};
+pub use association::{extract_extension_icon, extract_mime_icon};
+pub use background::spawn_extract;
+pub use batch::{ProgressReporter, StderrProgressReporter, extract_icons_batch};
+pub use cache::{extract_icon_to_cursor_dir, stable_icon_path};
+pub use config::ExtractionConfig;
+#[cfg(feature = "disk-cache")]
+pub use disk_cache::DiskCache;
+pub use embed::{extract_icon_as_data_uri, extract_icon_as_html_img_tag, extract_icon_svg_wrapped};
+pub use error::{AggregateError, IconError};
+pub use fallback::{ExtractionMethod, extract_icon_with_fallback_chain};
+pub use favicon::generate_favicon_set;
+pub use format::OutputFormat;
+pub use ico_file::{decode_ico, split_ico};
+pub use icon_group::extract_all_icon_groups_as_icos;
+pub use icon_iter::IconIterator;
+pub use launch::{extract_icon_and_open, open_with_shell};
+pub use location::{
+    parse_icon_location_string, to_windows_icon_spec, to_windows_icon_spec_for_resource_id,
+};
+pub use macos::generate_macos_icns;
+pub use metadata::{IconMetadata, IconWarning, extract_icon_with_metadata};
+pub use pe::{IconLocation, contains_icon, locate_icon_resource};
+pub use service::extract_service_icon;
+#[cfg(windows)]
+pub use size::extract_icon_for_window;
+pub use size::{
+    extract_icon_at_size, extract_icon_quality, extract_icon_thumbnail,
+    extract_icon_thumbnail_with_metadata, extract_icons_by_size,
+};
+pub use stock::{StockIcon, export_all_stock_icons, extract_stock_icon};
+pub use update::{copy_icon_between_executables, replace_icon_in_exe, strip_icon_from_exe};
+pub use verify::{IconAnomaly, IconVerificationResult, verify_icon_resources};
+
+/// Extracts the first icon and saves it as `icon.png` in `output_dir`.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use std::path::Path;
+/// # use icon_extractor::extract_icon;
+/// let exe = Path::new(r"C:\Windows\System32\notepad.exe");
+/// let result = extract_icon(exe, Path::new("."))?;
+/// println!("Saved {}", result.display());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn extract_icon(file_path: &Path, output_dir: &Path) -> Result<PathBuf> {
+    let img = extract_icon_image(file_path, 0)?;
+
+    let output_path = output_dir.join("icon.png");
+    img.save(&output_path)?;
+
+    Ok(output_path)
+}
+
+/// Extracts the icon once and writes `icon.<ext>` for each of `formats`.
+/// Formats sharing an extension, such as `Png` and `PngOptimized`, would
+/// overwrite each other, so asking for both is an error.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use std::path::Path;
+/// # use icon_extractor::{OutputFormat, extract_icon_to_formats};
+/// let formats = [OutputFormat::Png, OutputFormat::Ico];
+/// let exe = Path::new(r"C:\Windows\System32\notepad.exe");
+/// let result = extract_icon_to_formats(exe, Path::new("."), &formats)?;
+/// assert_eq!(result.len(), 2);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn extract_icon_to_formats(
+    file_path: &Path,
+    output_dir: &Path,
+    formats: &[OutputFormat],
+) -> Result<Vec<PathBuf>> {
+    for (i, format) in formats.iter().enumerate() {
+        if let Some(earlier) = formats[..i]
+            .iter()
+            .find(|earlier| earlier.extension() == format.extension())
+        {
+            anyhow::bail!(
+                "{earlier:?} and {format:?} would both be written to icon.{}; request one of them.",
+                format.extension()
+            );
+        }
+    }
+    let img = extract_icon_image(file_path, 0)?.into();
+    let config = ExtractionConfig::default();
+
+    formats
+        .iter()
+        .map(|format| {
+            let output_path = output_dir.join(format!("icon.{}", format.extension()));
+            std::fs::write(&output_path, format.encode(&img, &config)?)?;
+            Ok(output_path)
+        })
+        .collect()
+}
+
+/// Extracts the icon at `index` and encodes it in the given format.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use std::path::Path;
+/// # use icon_extractor::{OutputFormat, extract_icon_to_bytes};
+/// let exe = Path::new(r"C:\Windows\System32\notepad.exe");
+/// let result = extract_icon_to_bytes(exe, 0, OutputFormat::Png)?;
+/// assert!(result.starts_with(b"\x89PNG"));
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn extract_icon_to_bytes(
+    file_path: &Path,
+    index: u32,
+    format: OutputFormat,
+) -> Result<Vec<u8>> {
+    let img = extract_icon_image(file_path, index)?.into();
+    format.encode(&img, &ExtractionConfig::default())
+}
+
+/// Extracts the icon at `index` into a temporary file whose name ends with
+/// `suffix`, e.g. `.png`. The format follows the suffix's extension and falls
+/// back to PNG. The file is deleted on drop unless the caller `keep`s it.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use std::path::Path;
+/// # use icon_extractor::extract_icon_to_named_temp_file;
+/// let exe = Path::new(r"C:\Windows\System32\notepad.exe");
+/// let result = extract_icon_to_named_temp_file(exe, 0, ".png")?;
+/// println!("Icon at {}", result.path().display());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn extract_icon_to_named_temp_file(
+    file_path: &Path,
+    index: u32,
+    suffix: &str,
+) -> Result<NamedTempFile> {
+    let ext = suffix.rsplit('.').next().unwrap_or_default();
+    let format = OutputFormat::from_extension(ext).unwrap_or_default();
+    let bytes = extract_icon_to_bytes(file_path, index, format)?;
+
+    let mut temp_file = tempfile::Builder::new()
+        .prefix("icon_")
+        .suffix(suffix)
+        .tempfile()?;
+    temp_file.write_all(&bytes)?;
+    temp_file.flush()?;
+
+    Ok(temp_file)
+}
+
+/// Extracts the icon at `index` as a QOI image.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use std::path::Path;
+/// # use icon_extractor::extract_icon_as_qoi;
+/// let exe = Path::new(r"C:\Windows\System32\notepad.exe");
+/// let result = extract_icon_as_qoi(exe, 0)?;
+/// assert!(result.starts_with(b"qoif"));
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn extract_icon_as_qoi(file_path: &Path, index: u32) -> Result<Vec<u8>> {
+    extract_icon_to_bytes(file_path, index, OutputFormat::Qoi)
+}
+
+/// The smallest size `ExtractIconExW` hands out as a large icon on any
+/// current Windows, for `warn_on_suspicious_icon`.
+const MIN_LARGE_ICON_SIZE: u32 = 32;
+
+/// Extracts the icon at `index`, honoring the options in `config`.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use std::path::Path;
+/// # use icon_extractor::{ExtractionConfig, extract_icon_with_config};
+/// let mut config = ExtractionConfig::default();
+/// config.canvas = Some((64, 64));
+/// let exe = Path::new(r"C:\Windows\System32\notepad.exe");
+/// let result = extract_icon_with_config(exe, 0, &config)?;
+/// assert_eq!((result.width(), result.height()), (64, 64));
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn extract_icon_with_config(
+    file_path: &Path,
+    index: u32,
+    config: &ExtractionConfig,
+) -> Result<DynamicImage> {
+    extract_with_warnings(file_path, index, config).map(|(img, _)| img)
+}
+
+/// `extract_icon_with_config`, also returning the quality caveats collected
+/// along the way.
+pub(crate) fn extract_with_warnings(
+    file_path: &Path,
+    index: u32,
+    config: &ExtractionConfig,
+) -> Result<(DynamicImage, Vec<IconWarning>)> {
+    if let Some((width, height)) = config.canvas
+        && (width == 0 || height == 0)
+    {
+        anyhow::bail!("Canvas size {width}x{height} has no area; both sides must be at least 1.");
+    }
+
+    let index = ordinal(index)?;
+    let extracted = match config.extraction_timeout {
+        Some(timeout) => extract_large_icon_with_timeout(file_path, index, timeout),
+        None => extract_large_icon_with_warnings(file_path, index),
+    };
+    let (img, warnings) = match extracted {
+        Err(e)
+            if config.best_effort && matches!(e.downcast_ref(), Some(IconError::NoIcon { .. })) =>
+        {
+            let img = scan::scan_for_icon(file_path)?.ok_or(e)?;
+            (img, vec![IconWarning::Fallback])
+        }
+        extracted => extracted?,
+    };
+    if config.warn_on_suspicious_icon
+        && (img.width() < MIN_LARGE_ICON_SIZE || img.height() < MIN_LARGE_ICON_SIZE)
+    {
+        tracing::warn!(
+            "Large icon {index} of {} is only {}x{}; the file may only have a small icon",
+            file_path.display(),
+            img.width(),
+            img.height()
+        );
+    }
+    let mut img = DynamicImage::ImageRgba8(img);
+
+    if let Some((width, height)) = config.canvas {
+        img = convert::center_on_canvas(&img, width, height).into();
+    }
+
+    if config.grayscale {
+        img = convert::to_grayscale(&img.to_rgba8()).into();
+    }
+
+    if let Some(post_process) = &config.post_process {
+        img = post_process(img);
+    }
+
+    Ok((img, warnings))
+}
+
+fn extract_large_icon_with_timeout(
+    file_path: &Path,
+    index: i32,
+    timeout: Duration,
+) -> Result<(RgbaImage, Vec<IconWarning>)> {
+    let (tx, rx) = mpsc::channel();
+    let target_path = file_path.to_path_buf();
+    thread::spawn(move || {
+        _ = tx.send(extract_large_icon_with_warnings(&target_path, index));
+    });
+
+    match rx.recv_timeout(timeout) {
+        Ok(result) => result,
+        Err(RecvTimeoutError::Timeout) => Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!(
+                "Extraction timed out after {}ms: {}",
+                timeout.as_millis(),
+                file_path.display()
+            ),
+        )
+        .into()),
+        Err(RecvTimeoutError::Disconnected) => anyhow::bail!("Extraction thread panicked."),
+    }
+}
+
+/// Returns how many icons `ExtractIconExW` can extract from the file.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use std::path::Path;
+/// # use icon_extractor::icon_count;
+/// let result = icon_count(Path::new(r"C:\Windows\System32\shell32.dll"))?;
+/// println!("shell32.dll has {result} icons");
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[cfg(windows)]
+pub fn icon_count(file_path: &Path) -> Result<u32> {
+    let file_str = to_extended_path(&check_icon_file(file_path)?);
+    let count = unsafe { ExtractIconExW(file_str.as_ptr(), -1, null_mut(), null_mut(), 0) };
+    Ok(count)
+}
+
+/// Returns how many icons `ExtractIconExW` can extract from the file.
+/// Always fails with [`IconError::UnsupportedPlatform`] off Windows.
+#[cfg(not(windows))]
+pub fn icon_count(file_path: &Path) -> Result<u32> {
+    check_icon_file(file_path)?;
+    Err(IconError::UnsupportedPlatform.into())
+}
+
+/// Extracts the large icon at `index` into an RGBA buffer.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use std::path::Path;
+/// # use icon_extractor::extract_icon_image;
+/// let exe = Path::new(r"C:\Windows\System32\notepad.exe");
+/// let result = extract_icon_image(exe, 0)?;
+/// println!("{}x{}", result.width(), result.height());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn extract_icon_image(file_path: &Path, index: u32) -> Result<RgbaImage> {
+    extract_large_icon(file_path, ordinal(index)?)
+}
+
+/// Extracts an icon given in the `path,index` notation used by the registry
+/// and shortcuts, e.g. `C:\Windows\System32\shell32.dll,-13`. A negative index
+/// names a resource ID rather than an ordinal.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use std::path::Path;
+/// # use icon_extractor::extract_icon_from_absolute_resource_path;
+/// let result = extract_icon_from_absolute_resource_path(r"C:\Windows\System32\shell32.dll,-13")?;
+/// println!("{}x{}", result.width(), result.height());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn extract_icon_from_absolute_resource_path(resource_str: &str) -> Result<DynamicImage> {
+    let (file_path, index) = parse_icon_location_string(resource_str)?;
+    Ok(extract_large_icon(&file_path, index)?.into())
+}
+
+/// Extensions of the PE files `ExtractIconExW` is expected to read icons from.
+/// `.mui` files (e.g. `en-US\shell32.dll.mui`) are resource-only satellite
+/// DLLs, and some localized icons only live there.
+const SUPPORTED_EXTENSIONS: &[&str] = &["exe", "dll", "cpl", "ocx", "scr", "mui"];
+
+/// Converts a public `u32` icon ordinal to the `i32` Win32 takes, where
+/// anything past `i32::MAX` would wrap around into a resource ID.
+pub(crate) fn ordinal(index: u32) -> Result<i32> {
+    i32::try_from(index).map_err(|_| {
+        anyhow::anyhow!(
+            "Icon index {index} is out of range; indices go up to {}.",
+            i32::MAX
+        )
+    })
+}
+
+/// `index` follows `ExtractIconExW`: zero or more is an ordinal, negative is
+/// a resource ID.
+fn extract_large_icon(file_path: &Path, index: i32) -> Result<RgbaImage> {
+    extract_large_icon_with_warnings(file_path, index).map(|(img, _)| img)
+}
+
+#[cfg(windows)]
+pub(crate) fn extract_large_icon_with_warnings(
+    file_path: &Path,
+    index: i32,
+) -> Result<(RgbaImage, Vec<IconWarning>)> {
+    let file_str = to_extended_path(&check_icon_file(file_path)?);
+
+    unsafe {
+        let mut hicon_large: [HICON; 1] = [null_mut()];
+        // Not every failure path sets an error, so clear any stale one.
+        SetLastError(0);
+        let extracted = ExtractIconExW(
+            file_str.as_ptr(),
+            index,
+            hicon_large.as_mut_ptr(),
+            null_mut(),
+            1,
+        );
+        if extracted == 0 || hicon_large[0].is_null() {
+            return Err(extraction_error(file_path, index, GetLastError()).into());
+        }
+
+        let hicon = hicon_large[0];
+        let img = gdi::hicon_to_image_with_warnings(hicon);
+        DestroyIcon(hicon);
+        img
+    }
+}
+
+/// Maps the `GetLastError` code left by a failed `ExtractIconExW`, which
+/// loads the file with `LoadLibraryExW`, to the variant describing it.
+#[cfg(windows)]
+fn extraction_error(file_path: &Path, index: i32, code: u32) -> IconError {
+    let path = file_path.to_path_buf();
+    match code {
+        ERROR_FILE_NOT_FOUND | ERROR_PATH_NOT_FOUND | ERROR_MOD_NOT_FOUND => {
+            IconError::FileNotFound(path)
+        }
+        ERROR_BAD_EXE_FORMAT | ERROR_INVALID_EXE_SIGNATURE | ERROR_EXE_MARKED_INVALID => {
+            IconError::NotPeFile(path)
+        }
+        ERROR_ACCESS_DENIED | ERROR_SHARING_VIOLATION | ERROR_LOCK_VIOLATION => {
+            IconError::AccessDenied(path)
+        }
+        ERROR_SUCCESS
+        | ERROR_RESOURCE_DATA_NOT_FOUND
+        | ERROR_RESOURCE_TYPE_NOT_FOUND
+        | ERROR_RESOURCE_NAME_NOT_FOUND => IconError::NoIcon {
+            path,
+            index,
+            count: icon_count(file_path).ok(),
+        },
+        code => IconError::Win32 { path, code },
+    }
+}
+
+#[cfg(not(windows))]
+pub(crate) fn extract_large_icon_with_warnings(
+    file_path: &Path,
+    _index: i32,
+) -> Result<(RgbaImage, Vec<IconWarning>)> {
+    check_icon_file(file_path)?;
+    Err(IconError::UnsupportedPlatform.into())
+}
+
+/// Checks that `file_path` is a file icons can be read from and returns its
+/// canonical path.
+fn check_icon_file(file_path: &Path) -> Result<PathBuf> {
+    if !file_path.exists() {
+        return Err(IconError::FileNotFound(file_path.to_path_buf()).into());
+    }
+
+    let supported = file_path.extension().is_some_and(|ext| {
+        SUPPORTED_EXTENSIONS
+            .iter()
+            .any(|supported| ext.eq_ignore_ascii_case(supported))
+    });
+    if !supported || !has_mz_header(file_path) {
+        return Err(IconError::NotPeFile(file_path.to_path_buf()).into());
+    }
+
+    canonicalize(file_path)
+}
+
+fn has_mz_header(file_path: &Path) -> bool {
+    let mut magic = [0u8; 2];
+    std::fs::File::open(file_path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .is_ok_and(|()| &magic == b"MZ")
+}
+
+/// Resolves `path` to an absolute path with symlinks and `..` removed, so Win32
+/// calls don't depend on the working directory. The `\\?\` prefix added by
+/// `fs::canonicalize` is dropped again for drive paths; `to_extended_path`
+/// restores it when the path is actually too long.
+pub(crate) fn canonicalize(path: &Path) -> Result<PathBuf> {
+    let canonical = std::fs::canonicalize(path)
+        .with_context(|| format!("Failed to resolve path: {}", path.display()))?;
+
+    let simplified = canonical
+        .to_str()
+        .and_then(|s| s.strip_prefix(r"\\?\"))
+        .filter(|s| s.as_bytes().get(1) == Some(&b':'))
+        .map(PathBuf::from);
+
+    Ok(simplified.unwrap_or(canonical))
+}
+
+/// Encodes `path` as a NUL-terminated wide string for Win32 APIs, switching to
+/// the `\\?\` extended-length form once it no longer fits in `MAX_PATH`.
+#[cfg(windows)]
+pub(crate) fn to_extended_path(path: &Path) -> Vec<u16> {
+    let wide: Vec<u16> = path.as_os_str().encode_wide().collect();
+    let verbatim: Vec<u16> = r"\\?\".encode_utf16().collect();
+    if wide.len() < MAX_PATH || wide.starts_with(&verbatim) {
+        return wide.into_iter().chain(Some(0)).collect();
+    }
+
+    // The `\\?\` form is passed through untouched, so it must be absolute.
+    let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let absolute: Vec<u16> = absolute.as_os_str().encode_wide().collect();
+    let unc: Vec<u16> = r"\\".encode_utf16().collect();
+
+    let mut extended = verbatim;
+    if let Some(share) = absolute.strip_prefix(unc.as_slice()) {
+        extended.extend("UNC\\".encode_utf16());
+        extended.extend_from_slice(share);
+    } else {
+        extended.extend(absolute);
+    }
+    extended.push(0);
+    extended
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pe::fixture::{build_pe, write_temp};
+
+    #[test]
+    fn extract_icon_to_formats_rejects_repeated_extensions() {
+        let dir = tempfile::tempdir().unwrap();
+        let formats = [OutputFormat::Png, OutputFormat::Qoi, OutputFormat::Png];
+
+        let err = extract_icon_to_formats(Path::new("app.exe"), dir.path(), &formats).unwrap_err();
+
+        assert!(
+            err.to_string().contains("both be written to icon.png"),
+            "{err:#}"
+        );
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn indices_past_i32_max_are_rejected_not_wrapped() {
+        assert_eq!(ordinal(i32::MAX as u32).unwrap(), i32::MAX);
+        for index in [1 << 31, u32::MAX] {
+            let err = extract_icon_image(Path::new("app.exe"), index).unwrap_err();
+            assert!(err.to_string().contains("out of range"), "{err:#}");
+        }
+
+        let (_dir, path) = write_temp("app.exe", &build_pe(&[]));
+        let err =
+            extract_icon_with_config(&path, u32::MAX, &ExtractionConfig::default()).unwrap_err();
+        assert!(err.to_string().contains("out of range"), "{err:#}");
+    }
+
+    #[test]
+    fn mui_satellites_are_icon_files() {
+        for name in ["shell32.dll.mui", "SHELL32.DLL.MUI"] {
+            let (_dir, path) = write_temp(name, &build_pe(&[]));
+            assert!(check_icon_file(&path).is_ok(), "{name}");
+        }
+
+        let (_dir, path) = write_temp("shell32.dll.txt", &build_pe(&[]));
+        let err = check_icon_file(&path).unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(IconError::NotPeFile(_))));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn system_mui_files_can_be_opened() {
+        let root = std::env::var_os("SystemRoot").expect("SystemRoot is set");
+        let mui = Path::new(&root).join(r"System32\en-US\shell32.dll.mui");
+
+        // Satellites often hold no icons at all; what matters is that they're
+        // handed to ExtractIconExW rather than rejected up front.
+        icon_count(&mui).unwrap();
+    }
+}
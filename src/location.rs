@@ -0,0 +1,122 @@
+use crate::canonicalize;
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+/// Parses the `path,index` notation used by `HKCR\...\DefaultIcon` values and
+/// `IShellLink::GetIconLocation`.
+///
+/// The path may be quoted, and the index defaults to `0` when omitted. A
+/// negative index is a resource ID rather than an ordinal.
+pub fn parse_icon_location_string(s: &str) -> Result<(PathBuf, i32)> {
+    let s = s.trim();
+
+    let (path, index) = if let Some(quoted) = s.strip_prefix('"') {
+        let (path, rest) = quoted
+            .split_once('"')
+            .with_context(|| format!("Unterminated quote in icon location: {s}"))?;
+        let rest = rest.trim_start();
+        let index = if rest.is_empty() {
+            None
+        } else {
+            let index = rest
+                .strip_prefix(',')
+                .with_context(|| format!("Unexpected text after quoted path: {s}"))?;
+            Some(index)
+        };
+        (path, index)
+    } else {
+        match s.rsplit_once(',') {
+            // A comma followed by more path is part of the file name.
+            Some((_, tail)) if tail.contains(['\\', '/']) => (s, None),
+            Some((path, index)) => (path, Some(index)),
+            None => (s, None),
+        }
+    };
+
+    let path = path.trim();
+    if path.is_empty() {
+        anyhow::bail!("Missing path in icon location: {s}");
+    }
+
+    let index = match index {
+        Some(index) => index
+            .trim()
+            .parse()
+            .with_context(|| format!("Invalid icon index in icon location: {s}"))?,
+        None => 0,
+    };
+
+    Ok((PathBuf::from(path), index))
+}
+
+/// Formats the icon at ordinal `index` of `file_path` in the `path,index`
+/// notation, e.g. `C:\Program Files\App\app.exe,0` for a `DefaultIcon`
+/// value or a shortcut. The inverse of [`parse_icon_location_string`].
+pub fn to_windows_icon_spec(file_path: &Path, index: u32) -> Result<String> {
+    icon_spec(file_path, index as i64)
+}
+
+/// Like [`to_windows_icon_spec`], but names the icon by resource ID, which
+/// the notation writes as a negative index: `shell32.dll,-16769`.
+pub fn to_windows_icon_spec_for_resource_id(file_path: &Path, resource_id: u16) -> Result<String> {
+    icon_spec(file_path, -(resource_id as i64))
+}
+
+fn icon_spec(file_path: &Path, index: i64) -> Result<String> {
+    let path = canonicalize(file_path)?;
+    let path = path.to_string_lossy();
+
+    // Quoting keeps a comma in the path from being read as the separator.
+    if path.contains(',') {
+        Ok(format!("\"{path}\",{index}"))
+    } else {
+        Ok(format!("{path},{index}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> (PathBuf, i32) {
+        parse_icon_location_string(s).unwrap()
+    }
+
+    fn parse_err(s: &str) -> String {
+        parse_icon_location_string(s).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn parses_plain_and_quoted_locations() {
+        assert_eq!(
+            parse(r"C:\Windows\System32\shell32.dll,-16769"),
+            (PathBuf::from(r"C:\Windows\System32\shell32.dll"), -16769)
+        );
+        assert_eq!(
+            parse(r#" "C:\Program Files\App\app.exe" , 3 "#),
+            (PathBuf::from(r"C:\Program Files\App\app.exe"), 3)
+        );
+        assert_eq!(parse(r"C:\app.exe"), (PathBuf::from(r"C:\app.exe"), 0));
+    }
+
+    #[test]
+    fn comma_in_directory_name_is_part_of_the_path() {
+        assert_eq!(
+            parse(r"C:\Tools,Old\app.exe"),
+            (PathBuf::from(r"C:\Tools,Old\app.exe"), 0)
+        );
+        assert_eq!(
+            parse(r"C:\Tools,Old\app.exe,2"),
+            (PathBuf::from(r"C:\Tools,Old\app.exe"), 2)
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_locations() {
+        assert!(parse_err(r#""C:\app.exe,1"#).contains("Unterminated quote"));
+        assert!(parse_err(r#""C:\app.exe"x,1"#).contains("Unexpected text"));
+        assert!(parse_err(",1").contains("Missing path"));
+        assert!(parse_err(r#""",1"#).contains("Missing path"));
+        assert!(parse_err(r"C:\app.exe,one").contains("Invalid icon index"));
+    }
+}
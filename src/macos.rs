@@ -0,0 +1,29 @@
+use crate::extract_icon_at_size;
+use anyhow::Result;
+use icns::{IconFamily, Image, PixelFormat};
+use image::imageops::{self, FilterType};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+/// The image sizes stored in a macOS app icon.
+const ICNS_SIZES: &[u32] = &[16, 32, 64, 128, 256, 512];
+
+/// Writes the icon at `index` as an Apple ICNS file at `output_path`, with an
+/// image at each size a macOS app icon uses.
+pub fn generate_macos_icns(file_path: &Path, index: u32, output_path: &Path) -> Result<()> {
+    let mut family = IconFamily::new();
+    for &size in ICNS_SIZES {
+        let mut img = extract_icon_at_size(file_path, index, size)?;
+        // ICNS types have fixed dimensions; make sure the shell didn't pick
+        // a nearby size instead.
+        if img.width() != size || img.height() != size {
+            img = imageops::resize(&img, size, size, FilterType::Lanczos3);
+        }
+        let image = Image::from_data(PixelFormat::RGBA, size, size, img.into_raw())?;
+        family.add_icon(&image)?;
+    }
+
+    family.write(BufWriter::new(File::create(output_path)?))?;
+    Ok(())
+}
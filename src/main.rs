@@ -1,149 +1,443 @@
-#[cfg(not(windows))]
-compile_error!("icon_extractor only supports Windows platform.");
-
-use anyhow::Result;
-use image::{ImageBuffer, Rgba};
+use anyhow::{Context, Result};
+use icon_extractor::{
+    ExtractionConfig, IconError, extract_icon_at_size, extract_icon_with_metadata, open_with_shell,
+    unpack_zip_entry,
+};
 use std::env;
-use std::os::windows::ffi::OsStrExt;
-use std::path::Path;
-use std::path::PathBuf;
-use std::process::Command;
-use std::ptr::null_mut;
-use tempfile::tempdir;
-use winapi::shared::windef::HICON;
-use winapi::um::shellapi::ExtractIconExW;
-use winapi::um::wingdi::{BITMAP, BITMAPINFO, BITMAPINFOHEADER, GetObjectW};
-use winapi::um::wingdi::{DIB_RGB_COLORS, GetDIBits};
-use winapi::um::winuser::{DestroyIcon, GetDC, GetIconInfo, ReleaseDC};
-
-pub fn extract_icon(file_path: &Path, output_dir: &Path) -> Result<PathBuf> {
-    let satisfied = file_path.exists()
-        && file_path
-            .extension()
-            .map_or(false, |ext| ext.eq_ignore_ascii_case("exe"));
-
-    if !satisfied {
-        anyhow::bail!(
-            "The provided file is not a valid executable: {}",
-            file_path.display()
-        );
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+use tempfile::tempdir_in;
+
+#[cfg(feature = "shell")]
+mod shell;
+
+/// Process exit codes, so scripts can branch on why extraction failed. Keep
+/// in sync with the usage text and README.
+#[derive(Debug, Clone, Copy)]
+enum Exit {
+    Argument = 1,
+    FileNotFound = 2,
+    NotPeFile = 3,
+    NoIcon = 4,
+    /// GDI or another Win32 call failed.
+    Api = 5,
+    Output = 6,
+}
+
+struct Failure {
+    exit: Exit,
+    error: anyhow::Error,
+}
+
+trait OrExit<T> {
+    /// Fails with `exit` regardless of the error.
+    fn exit_with(self, exit: Exit) -> Result<T, Failure>;
+
+    /// Fails with the code matching the error, or `fallback` if it doesn't
+    /// say what went wrong.
+    fn classify_or(self, fallback: Exit) -> Result<T, Failure>;
+}
+
+impl<T> OrExit<T> for Result<T> {
+    fn exit_with(self, exit: Exit) -> Result<T, Failure> {
+        self.map_err(|error| Failure { exit, error })
     }
 
-    let target_path = file_path.to_path_buf();
-    let file_str: Vec<u16> = target_path
-        .as_os_str()
-        .encode_wide()
-        .chain(Some(0))
-        .collect();
-
-    unsafe {
-        let mut hicon_large: [HICON; 1] = [null_mut()];
-        let extracted = ExtractIconExW(
-            file_str.as_ptr(),
-            0,
-            hicon_large.as_mut_ptr(),
-            null_mut(),
-            1,
-        );
-        if extracted == 0 || hicon_large[0].is_null() {
-            anyhow::bail!("ExtractIconExW failed for file: {}", target_path.display());
-        }
+    fn classify_or(self, fallback: Exit) -> Result<T, Failure> {
+        self.map_err(|error| Failure {
+            exit: classify(&error).unwrap_or(fallback),
+            error,
+        })
+    }
+}
+
+fn classify(error: &anyhow::Error) -> Option<Exit> {
+    if let Some(error) = error.downcast_ref::<IconError>() {
+        return match error {
+            IconError::FileNotFound(_) => Some(Exit::FileNotFound),
+            IconError::NotPeFile(_) => Some(Exit::NotPeFile),
+            IconError::NoIcon { .. } => Some(Exit::NoIcon),
+            _ => None,
+        };
+    }
 
-        let hicon = hicon_large[0];
+    match error.downcast_ref::<io::Error>() {
+        Some(error) if error.kind() == io::ErrorKind::NotFound => Some(Exit::FileNotFound),
+        _ => None,
+    }
+}
+
+enum Input {
+    File(PathBuf),
+    /// An executable stored inside a ZIP archive.
+    Zip {
+        archive: PathBuf,
+        entry: String,
+    },
+}
 
-        let mut icon_info = std::mem::zeroed();
-        if GetIconInfo(hicon, &mut icon_info) == 0 {
-            DestroyIcon(hicon);
-            anyhow::bail!("GetIconInfo failed.");
+impl Input {
+    /// Name of the file the icon comes from, used for the default output name.
+    fn source_name(&self) -> &Path {
+        match self {
+            Input::File(path) => path,
+            Input::Zip { entry, .. } => Path::new(entry),
         }
+    }
+}
 
-        let mut bmp: BITMAP = std::mem::zeroed();
-        if GetObjectW(
-            icon_info.hbmColor as _,
-            std::mem::size_of::<BITMAP>() as i32,
-            &mut bmp as *mut _ as _,
-        ) == 0
-        {
-            DestroyIcon(hicon);
-            anyhow::bail!("GetObjectW failed.");
+struct Args {
+    input: Input,
+    output: Option<PathBuf>,
+    open: bool,
+    verbose: bool,
+    /// Sizes from `--size-list`; empty means a single icon at the native size.
+    sizes: Vec<u32>,
+    config: ExtractionConfig,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<Args>> {
+    let mut file_path = None;
+    let mut zip_path = None;
+    let mut entry = None;
+    let mut output = None;
+    let mut open = false;
+    let mut verbose = false;
+    let mut sizes = Vec::new();
+    let mut config = ExtractionConfig::default();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => {
+                let value = args
+                    .next()
+                    .with_context(|| format!("{arg} requires a value"))?;
+                output = Some(PathBuf::from(value));
+            }
+            "--open" => open = true,
+            "-v" | "--verbose" => verbose = true,
+            "--from-zip" => {
+                let value = args.next().context("--from-zip requires a value")?;
+                zip_path = Some(PathBuf::from(value));
+            }
+            "--entry" => entry = Some(args.next().context("--entry requires a value")?),
+            "--timeout-ms" => {
+                let value = args.next().context("--timeout-ms requires a value")?;
+                let ms: u64 = value
+                    .parse()
+                    .with_context(|| format!("Invalid --timeout-ms value: {value}"))?;
+                config.extraction_timeout = Some(Duration::from_millis(ms));
+            }
+            "--size-list" => {
+                let value = args.next().context("--size-list requires a value")?;
+                sizes = parse_size_list(&value)?;
+            }
+            _ if arg.starts_with('-') => anyhow::bail!("Unknown option: {arg}"),
+            _ if file_path.is_none() => file_path = Some(PathBuf::from(arg)),
+            _ => anyhow::bail!("Unexpected argument: {arg}"),
         }
-        let width = bmp.bmWidth as usize;
-        let height = bmp.bmHeight as usize;
-
-        let mut bmp_info = BITMAPINFO {
-            bmiHeader: BITMAPINFOHEADER {
-                biSize: std::mem::size_of::<BITMAPINFOHEADER>() as u32,
-                biWidth: width as i32,
-                biHeight: -(height as i32), // 负表示自顶向下
-                biPlanes: 1,
-                biBitCount: 32,
-                biCompression: 0, // BI_RGB
-                biSizeImage: 0,
-                biXPelsPerMeter: 0,
-                biYPelsPerMeter: 0,
-                biClrUsed: 0,
-                biClrImportant: 0,
-            },
-            bmiColors: [std::mem::zeroed(); 1],
-        };
+    }
 
-        let mut pixels = vec![0u8; width * height * 4];
-
-        let hdc = GetDC(null_mut());
-        let ret = GetDIBits(
-            hdc,
-            icon_info.hbmColor,
-            0,
-            height as u32,
-            pixels.as_mut_ptr() as _,
-            &mut bmp_info,
-            DIB_RGB_COLORS,
-        );
-        ReleaseDC(null_mut(), hdc);
+    let input = match (file_path, zip_path, entry) {
+        (None, None, None) => return Ok(None),
+        (Some(path), None, None) => Input::File(path),
+        (None, Some(archive), Some(entry)) => Input::Zip { archive, entry },
+        (None, Some(_), None) => anyhow::bail!("--from-zip requires --entry"),
+        (_, None, Some(_)) => anyhow::bail!("--entry can only be used with --from-zip"),
+        (Some(_), Some(_), _) => anyhow::bail!("Give either a file or --from-zip, not both"),
+    };
+    if open && !sizes.is_empty() {
+        anyhow::bail!("--open can't be combined with --size-list");
+    }
+    if config.extraction_timeout.is_some() && !sizes.is_empty() {
+        anyhow::bail!("--timeout-ms can't be combined with --size-list");
+    }
+
+    Ok(Some(Args {
+        input,
+        output,
+        open,
+        verbose,
+        sizes,
+        config,
+    }))
+}
 
-        if ret == 0 {
-            DestroyIcon(hicon);
-            anyhow::bail!("GetDIBits failed.");
+/// Parses `16,32,48`, dropping repeated sizes.
+fn parse_size_list(value: &str) -> Result<Vec<u32>> {
+    let mut sizes = Vec::new();
+    for entry in value.split(',') {
+        let size: u32 = entry
+            .trim()
+            .parse()
+            .ok()
+            .filter(|&size| size > 0)
+            .with_context(|| format!("Invalid size in --size-list: {entry:?}"))?;
+        if !sizes.contains(&size) {
+            sizes.push(size);
         }
+    }
+    Ok(sizes)
+}
 
-        let img: ImageBuffer<Rgba<u8>, _> =
-            ImageBuffer::from_raw(width as u32, height as u32, pixels)
-                .ok_or_else(|| anyhow::anyhow!("Failed to create ImageBuffer"))?;
+/// `-o` wins; otherwise `--open` writes to a temp dir that outlives the
+/// process so the viewer can show it, and the default is `<stem>.png` in the
+/// working directory.
+fn output_path(args: &Args) -> Result<PathBuf> {
+    output_path_in(args, &env::temp_dir())
+}
 
-        let output_path = output_dir.join("icon.png");
-        img.save(&output_path)?;
+/// `output_path`, with `--open` output going to a new directory in `temp_root`.
+fn output_path_in(args: &Args, temp_root: &Path) -> Result<PathBuf> {
+    if let Some(output) = &args.output {
+        return Ok(output.clone());
+    }
 
-        DestroyIcon(hicon);
+    if args.open {
+        let mut temp_dir = tempdir_in(temp_root).context(
+            "Failed to create a temporary directory for --open; use -o <file> to choose where the icon is written",
+        )?;
+        temp_dir.disable_cleanup(true);
+        return Ok(temp_dir.path().join("icon.png"));
+    }
 
-        winapi::um::wingdi::DeleteObject(icon_info.hbmColor as _);
-        winapi::um::wingdi::DeleteObject(icon_info.hbmMask as _);
+    let stem = args
+        .input
+        .source_name()
+        .file_stem()
+        .map(|s| s.to_string_lossy())
+        .unwrap_or_else(|| "icon".into());
+    Ok(PathBuf::from(format!("{stem}.png")))
+}
 
-        Ok(output_path)
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(Failure { exit, error }) => {
+            eprintln!("Error: {error:?}");
+            ExitCode::from(exit as u8)
+        }
     }
 }
 
-fn main() -> Result<()> {
-    let args: Vec<String> = env::args().collect();
-    if args.len() < 2 {
-        let exe = Path::new(&args[0])
+fn run() -> Result<(), Failure> {
+    let mut args = env::args().peekable();
+    let arg0 = args.next().unwrap_or_default();
+
+    if args.peek().is_some_and(|arg| arg == "shell") {
+        #[cfg(feature = "shell")]
+        return shell::run().exit_with(Exit::Api);
+        #[cfg(not(feature = "shell"))]
+        return Err(anyhow::anyhow!(
+            "This build has no `shell` subcommand; rebuild with the `shell` feature"
+        ))
+        .exit_with(Exit::Argument);
+    }
+
+    let Some(args) = parse_args(args).exit_with(Exit::Argument)? else {
+        let exe = Path::new(&arg0)
             .file_stem()
             .map(|s| s.to_string_lossy())
             .unwrap_or_else(|| "icon_extractor".into());
         eprintln!(
             "Extract icons from executable files
 
-Usage: {exe} <path-to-file>"
+Usage: {exe} [options] <path-to-file>
+       {exe} [options] --from-zip <zip> --entry <path-in-zip>
+       {exe} shell
+
+Options:
+  -o, --output <file>  Write the icon here (default: <stem>.png in the current directory)
+  --open               Open the icon in the default viewer (written to a temp dir unless -o is given)
+  --from-zip <zip>     Read the executable from a ZIP archive, see --entry
+  --entry <path>       Path of the executable inside the --from-zip archive
+  --timeout-ms <n>     Give up if extraction takes longer than <n> milliseconds
+  --size-list <sizes>  Render each size in a list like 16,32,48 to icon_<size>.png,
+                       in the -o directory if given; not with --open or --timeout-ms
+  -v, --verbose        Print extra progress information and quality warnings
+
+Exit codes:
+  0  Success
+  1  Invalid arguments
+  2  File not found
+  3  Not a PE file
+  4  No icon at the requested index
+  5  GDI/Win32 API failure
+  6  Failed to write the output"
         );
         return Ok(());
+    };
+
+    // Keeps an unpacked ZIP entry on disk until extraction is done.
+    let _unpacked;
+    let file_path = match &args.input {
+        Input::File(path) => path.clone(),
+        Input::Zip { archive, entry } => {
+            let temp_path = unpack_zip_entry(archive, entry).classify_or(Exit::FileNotFound)?;
+            if args.verbose {
+                eprintln!("Unpacked {entry} to {}", temp_path.display());
+            }
+            let path = temp_path.to_path_buf();
+            _unpacked = temp_path;
+            path
+        }
+    };
+
+    if !args.sizes.is_empty() {
+        return extract_sizes(&file_path, &args);
     }
 
-    let file_path = Path::new(&args[1]);
-    let mut temp_dir = tempdir()?;
-    temp_dir.disable_cleanup(true);
+    let (img, metadata) =
+        extract_icon_with_metadata(&file_path, 0, &args.config).classify_or(Exit::Api)?;
+    if args.verbose {
+        for warning in &metadata.warnings {
+            eprintln!("Warning: {warning}");
+        }
+    }
+    let icon_path = output_path(&args).exit_with(Exit::Output)?;
+    img.save(&icon_path)
+        .map_err(anyhow::Error::from)
+        .exit_with(Exit::Output)?;
 
-    let icon_path = extract_icon(file_path, temp_dir.path())?;
-    _ = Command::new("explorer").arg(&icon_path).status();
+    if args.open {
+        _ = open_with_shell(&icon_path);
+    }
     println!("Icon extracted to: {}", icon_path.display());
 
     Ok(())
 }
+
+/// Writes `icon_<size>.png` for each `--size-list` entry, into the `-o`
+/// directory or the working directory.
+fn extract_sizes(file_path: &Path, args: &Args) -> Result<(), Failure> {
+    let out_dir = args.output.clone().unwrap_or_default();
+
+    for &size in &args.sizes {
+        let img = extract_icon_at_size(file_path, 0, size).classify_or(Exit::Api)?;
+        let icon_path = out_dir.join(format!("icon_{size}.png"));
+        img.save(&icon_path)
+            .map_err(anyhow::Error::from)
+            .exit_with(Exit::Output)?;
+        println!("Icon extracted to: {}", icon_path.display());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Option<Args>> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    fn args_for(input: Input) -> Args {
+        Args {
+            input,
+            output: None,
+            open: false,
+            verbose: false,
+            sizes: Vec::new(),
+            config: ExtractionConfig::default(),
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_options() {
+        for arg in ["--help", "--otuput", "-x", "-"] {
+            let err = parse(&[arg, "app.exe"]).err().expect(arg);
+            assert_eq!(err.to_string(), format!("Unknown option: {arg}"));
+        }
+    }
+
+    #[test]
+    fn takes_the_first_plain_argument_as_the_file() {
+        let args = parse(&["-v", "app.exe", "-o", "out.png"]).unwrap().unwrap();
+        assert!(matches!(&args.input, Input::File(path) if path == Path::new("app.exe")));
+        assert_eq!(args.output, Some(PathBuf::from("out.png")));
+        assert!(args.verbose);
+    }
+
+    #[test]
+    fn size_list_drops_repeats() {
+        assert_eq!(parse_size_list("16,32, 16,48,32").unwrap(), [16, 32, 48]);
+    }
+
+    #[test]
+    fn size_list_rejects_bad_entries() {
+        for value in ["16,abc", "16,,32", "0", "-16", ""] {
+            let err = parse_size_list(value).expect_err(value);
+            assert!(err.to_string().starts_with("Invalid size in --size-list"));
+        }
+    }
+
+    #[test]
+    fn size_list_rejects_options_it_ignores() {
+        for option in [&["--open"][..], &["--timeout-ms", "100"]] {
+            let args = [&["app.exe", "--size-list", "16"][..], option].concat();
+            let err = parse(&args).err().expect(option[0]);
+            assert!(
+                err.to_string()
+                    .contains("can't be combined with --size-list")
+            );
+        }
+    }
+
+    #[test]
+    fn output_defaults_to_the_stem_in_the_working_directory() {
+        let args = args_for(Input::File(PathBuf::from("dir/foo.exe")));
+        assert_eq!(output_path(&args).unwrap(), PathBuf::from("foo.png"));
+
+        let args = args_for(Input::Zip {
+            archive: PathBuf::from("tools.zip"),
+            entry: "bin/bar.exe".to_string(),
+        });
+        assert_eq!(output_path(&args).unwrap(), PathBuf::from("bar.png"));
+    }
+
+    #[test]
+    fn output_option_wins() {
+        let mut args = args_for(Input::File(PathBuf::from("dir/foo.exe")));
+        args.output = Some(PathBuf::from("out/icon.png"));
+        args.open = true;
+        assert_eq!(output_path(&args).unwrap(), PathBuf::from("out/icon.png"));
+    }
+
+    #[test]
+    fn open_explains_temp_dir_failures() {
+        let missing = tempfile::tempdir().unwrap().path().join("missing");
+        let mut args = args_for(Input::File(PathBuf::from("foo.exe")));
+
+        // Without --open the temp dir isn't needed, so its absence is fine.
+        assert_eq!(
+            output_path_in(&args, &missing).unwrap(),
+            PathBuf::from("foo.png")
+        );
+
+        args.open = true;
+        let err = output_path_in(&args, &missing).unwrap_err();
+        assert!(err.to_string().contains("use -o <file>"), "{err:#}");
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn size_list_writes_one_png_per_size() {
+        use image::GenericImageView;
+
+        let root = std::env::var_os("SystemRoot").expect("SystemRoot is set");
+        let shell32 = Path::new(&root).join(r"System32\shell32.dll");
+        let out_dir = tempfile::tempdir().unwrap();
+        let mut args = args_for(Input::File(shell32.clone()));
+        args.output = Some(out_dir.path().to_path_buf());
+        args.sizes = vec![16, 32, 48];
+
+        assert!(extract_sizes(&shell32, &args).is_ok());
+        for size in [16, 32, 48] {
+            let img = image::open(out_dir.path().join(format!("icon_{size}.png"))).unwrap();
+            assert_eq!(img.dimensions(), (size, size));
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use crate::{ExtractionConfig, extract_with_warnings};
+use anyhow::Result;
+use image::{DynamicImage, RgbaImage};
+use std::fmt;
+use std::path::Path;
+
+/// Pixels at or below this alpha are treated as empty by `alpha_coverage`, so
+/// faint anti-aliasing halos don't count as content.
+const COVERAGE_ALPHA_THRESHOLD: u8 = 16;
+
+/// Facts about an extracted icon that callers would otherwise have to
+/// compute from the pixels themselves.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct IconMetadata {
+    /// Width of the image in pixels.
+    pub width: u32,
+    /// Height of the image in pixels.
+    pub height: u32,
+    /// Fraction of pixels that are visibly non-transparent, from 0.0 to 1.0.
+    /// Values near 0.0 flag degenerate, mostly blank icons.
+    pub alpha_coverage: f32,
+    /// Quality caveats of an extraction that still succeeded.
+    pub warnings: Vec<IconWarning>,
+}
+
+/// Something about an extracted icon that makes it worse than the file's
+/// best image, without making extraction fail.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum IconWarning {
+    /// The image was enlarged from a smaller one, so it looks blurry or blocky.
+    Upscaled {
+        /// Size of the source image in pixels.
+        from: u32,
+        /// Size it was enlarged to.
+        to: u32,
+    },
+    /// The icon has no alpha channel; transparency was rebuilt from its
+    /// 1-bit mask, so edges are jagged.
+    AlphaFromMask,
+    /// The source image has fewer than 32 bits per pixel and was converted.
+    LowColorDepth {
+        /// Bits per pixel of the source image.
+        bits: u16,
+    },
+    /// No icon resource was found; the image was scanned out of the file
+    /// (see `ExtractionConfig::best_effort`).
+    Fallback,
+}
+
+impl fmt::Display for IconWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IconWarning::Upscaled { from, to } => write!(f, "upscaled from {from}px to {to}px"),
+            IconWarning::AlphaFromMask => f.write_str("transparency rebuilt from the icon mask"),
+            IconWarning::LowColorDepth { bits } => write!(f, "converted from a {bits}-bit image"),
+            IconWarning::Fallback => {
+                f.write_str("found by scanning the file, not as an icon resource")
+            }
+        }
+    }
+}
+
+impl IconMetadata {
+    /// Computes the metadata of `img`, with no warnings.
+    #[must_use]
+    pub fn from_image(img: &RgbaImage) -> IconMetadata {
+        let total = img.width() as u64 * img.height() as u64;
+        let covered = img
+            .pixels()
+            .filter(|pixel| pixel[3] > COVERAGE_ALPHA_THRESHOLD)
+            .count() as u64;
+
+        IconMetadata {
+            width: img.width(),
+            height: img.height(),
+            alpha_coverage: if total == 0 {
+                0.0
+            } else {
+                (covered as f64 / total as f64) as f32
+            },
+            warnings: Vec::new(),
+        }
+    }
+}
+
+/// Extracts the icon at `index` like `extract_icon_with_config`, along with
+/// its [`IconMetadata`].
+pub fn extract_icon_with_metadata(
+    file_path: &Path,
+    index: u32,
+    config: &ExtractionConfig,
+) -> Result<(DynamicImage, IconMetadata)> {
+    let (img, warnings) = extract_with_warnings(file_path, index, config)?;
+    let metadata = IconMetadata {
+        warnings,
+        ..IconMetadata::from_image(&img.to_rgba8())
+    };
+    Ok((img, metadata))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn opaque_image_is_fully_covered() {
+        let metadata =
+            IconMetadata::from_image(&RgbaImage::from_pixel(32, 32, Rgba([10, 20, 30, 255])));
+
+        assert_eq!((metadata.width, metadata.height), (32, 32));
+        assert!((metadata.alpha_coverage - 1.0).abs() < f32::EPSILON);
+        assert!(metadata.warnings.is_empty());
+    }
+
+    #[test]
+    fn round_icon_coverage_ignores_faint_halo() {
+        // A filled disc with a faint halo around it, like a typical round
+        // application icon; the halo is below the coverage threshold.
+        let img = RgbaImage::from_fn(48, 48, |x, y| {
+            let (dx, dy) = (x as f32 - 23.5, y as f32 - 23.5);
+            match (dx * dx + dy * dy).sqrt() {
+                d if d < 20.0 => Rgba([200, 40, 40, 255]),
+                d if d < 22.0 => Rgba([200, 40, 40, 10]),
+                _ => Rgba([0, 0, 0, 0]),
+            }
+        });
+        let coverage = IconMetadata::from_image(&img).alpha_coverage;
+
+        // pi * 20^2 / 48^2
+        assert!((coverage - 0.545).abs() < 0.02, "coverage {coverage}");
+    }
+
+    #[test]
+    fn empty_image_has_no_coverage() {
+        assert_eq!(
+            IconMetadata::from_image(&RgbaImage::new(0, 0)).alpha_coverage,
+            0.0
+        );
+        assert_eq!(
+            IconMetadata::from_image(&RgbaImage::new(16, 16)).alpha_coverage,
+            0.0
+        );
+    }
+}
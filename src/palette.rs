@@ -0,0 +1,108 @@
+use color_quant::NeuQuant;
+use image::RgbaImage;
+use std::collections::HashMap;
+
+/// Most colors a PNG palette holds.
+pub(crate) const MAX_COLORS: usize = 256;
+
+/// An image reduced to at most 256 colors. Entry 0 of `palette` is reserved
+/// for fully transparent pixels.
+pub(crate) struct Indexed {
+    pub palette: Vec<[u8; 4]>,
+    pub indices: Vec<u8>,
+}
+
+/// Maps `img` onto a palette of at most `max_colors` entries (2 to
+/// `MAX_COLORS`), including the transparent one. Icons that already fit,
+/// such as legacy 8-bit ones, keep their exact colors; anything else is
+/// quantized with NeuQuant.
+pub(crate) fn index_image(img: &RgbaImage, max_colors: usize) -> Indexed {
+    let max_colors = max_colors.clamp(2, MAX_COLORS);
+    let mut palette = vec![[0, 0, 0, 0]];
+    let mut lookup = HashMap::new();
+
+    for pixel in img.pixels() {
+        if pixel[3] == 0 || lookup.contains_key(&pixel.0) {
+            continue;
+        }
+        if palette.len() == max_colors {
+            return quantize(img, max_colors);
+        }
+        lookup.insert(pixel.0, palette.len() as u8);
+        palette.push(pixel.0);
+    }
+
+    let indices = img
+        .pixels()
+        .map(|pixel| match pixel[3] {
+            0 => 0,
+            _ => lookup[&pixel.0],
+        })
+        .collect();
+
+    Indexed { palette, indices }
+}
+
+fn quantize(img: &RgbaImage, max_colors: usize) -> Indexed {
+    let visible: Vec<u8> = img
+        .pixels()
+        .filter(|pixel| pixel[3] != 0)
+        .flat_map(|pixel| pixel.0)
+        .collect();
+    let quant = NeuQuant::new(10, max_colors - 1, &visible);
+
+    let mut palette = vec![[0, 0, 0, 0]];
+    palette.extend(
+        quant
+            .color_map_rgba()
+            .chunks_exact(4)
+            .map(|c| [c[0], c[1], c[2], c[3]]),
+    );
+
+    let indices = img
+        .pixels()
+        .map(|pixel| match pixel[3] {
+            0 => 0,
+            _ => quant.index_of(&pixel.0) as u8 + 1,
+        })
+        .collect();
+
+    Indexed { palette, indices }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn transparent_pixels_use_entry_zero() {
+        let img = RgbaImage::from_fn(4, 1, |x, _| match x {
+            0 | 2 => Rgba([1, 2, 3, 0]),
+            1 => Rgba([10, 20, 30, 255]),
+            _ => Rgba([40, 50, 60, 128]),
+        });
+        let indexed = index_image(&img, MAX_COLORS);
+
+        assert_eq!(
+            indexed.palette,
+            [[0, 0, 0, 0], [10, 20, 30, 255], [40, 50, 60, 128]]
+        );
+        assert_eq!(indexed.indices, [0, 1, 0, 2]);
+    }
+
+    #[test]
+    fn quantizes_to_max_colors() {
+        let img = RgbaImage::from_fn(16, 16, |x, y| Rgba([x as u8 * 16, y as u8 * 16, 0, 255]));
+        let indexed = index_image(&img, 8);
+
+        assert!(indexed.palette.len() <= 8);
+        assert_eq!(indexed.palette[0], [0, 0, 0, 0]);
+        assert!(
+            indexed
+                .indices
+                .iter()
+                .all(|&i| i != 0 && (i as usize) < indexed.palette.len())
+        );
+    }
+}
@@ -0,0 +1,476 @@
+use crate::IconError;
+use anyhow::{Context, Result};
+use object::FileKind;
+use object::pe::{
+    ImageDosHeader, ImageNtHeaders32, ImageNtHeaders64, ImageResourceDataEntry,
+    ImageResourceDirectoryEntry, RT_GROUP_ICON, RT_ICON,
+};
+use object::read::coff::SectionTable;
+use object::read::pe::{ImageNtHeaders, ResourceDirectory, ResourceNameOrId};
+use std::fmt;
+use std::path::Path;
+
+/// Checks whether the file has any icon resources by reading its PE resource
+/// directory. Nothing is loaded or rendered, so this is cheap for bulk scans.
+pub fn contains_icon(file_path: &Path) -> Result<bool> {
+    let data = std::fs::read(file_path)?;
+    let Some(resources) = Resources::parse_file(file_path, &data)? else {
+        return Ok(false);
+    };
+
+    let root = resources.directory.root()?;
+    Ok(root
+        .entries
+        .iter()
+        .any(|entry| matches!(entry.name_or_id().id(), Some(RT_ICON | RT_GROUP_ICON))))
+}
+
+/// Where an icon image's bytes live in a PE file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct IconLocation {
+    /// Address of the data once the image is loaded.
+    pub rva: u32,
+    /// Offset of the data within the file.
+    pub file_offset: u64,
+    /// Length of the data in bytes.
+    pub size: u32,
+}
+
+/// Locates icon image `index`, counting `RT_ICON` resources in resource
+/// order, so other tools can patch it in place. The bytes at the location are
+/// a BMP (without file header) or a PNG.
+pub fn locate_icon_resource(file_path: &Path, index: usize) -> Result<IconLocation> {
+    let data = std::fs::read(file_path)?;
+    let resources = Resources::parse_file(file_path, &data)?;
+    let icons = match &resources {
+        Some(resources) => resources.of_type(RT_ICON)?,
+        None => Vec::new(),
+    };
+    let found = resources.as_ref().and_then(|resources| {
+        let icon = icons.get(index)?;
+        Some((icon.rva, resources.file_offset(icon.rva)?, icon.data.len()))
+    });
+    let Some((rva, file_offset, size)) = found else {
+        return Err(IconError::NoIcon {
+            path: file_path.to_path_buf(),
+            index: index as i32,
+            count: Some(icons.len() as u32),
+        }
+        .into());
+    };
+
+    Ok(IconLocation {
+        rva,
+        file_offset: file_offset as u64,
+        size: size as u32,
+    })
+}
+
+/// The name of a resource: most are numbered, some (e.g. Delphi's `MAINICON`)
+/// are named.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ResourceKey {
+    Id(u16),
+    Name(String),
+}
+
+impl fmt::Display for ResourceKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResourceKey::Id(id) => write!(f, "{id}"),
+            ResourceKey::Name(name) => f.write_str(name),
+        }
+    }
+}
+
+/// One resource, in one language.
+pub(crate) struct Resource<'data> {
+    pub key: ResourceKey,
+    pub language: u16,
+    pub rva: u32,
+    pub data: &'data [u8],
+}
+
+/// A PE file's resource tree, along with the section table needed to find the
+/// data its entries point to.
+pub(crate) struct Resources<'data> {
+    data: &'data [u8],
+    directory: ResourceDirectory<'data>,
+    sections: SectionTable<'data>,
+}
+
+impl<'data> Resources<'data> {
+    /// Returns `None` for PE files without a resource directory.
+    fn parse(data: &'data [u8]) -> Result<Option<Resources<'data>>> {
+        let parsed = match FileKind::parse(data)? {
+            FileKind::Pe32 => parse_resources::<ImageNtHeaders32>(data)?,
+            FileKind::Pe64 => parse_resources::<ImageNtHeaders64>(data)?,
+            _ => anyhow::bail!("Not a PE file."),
+        };
+        Ok(parsed.map(|(directory, sections)| Resources {
+            data,
+            directory,
+            sections,
+        }))
+    }
+
+    /// Reads the resource directory of `data`, the contents of `file_path`.
+    /// Returns `None` for PE files without one; anything that isn't a PE file
+    /// fails with [`IconError::NotPeFile`] rather than a parser error.
+    pub(crate) fn parse_file(
+        file_path: &Path,
+        data: &'data [u8],
+    ) -> Result<Option<Resources<'data>>> {
+        if !matches!(FileKind::parse(data), Ok(FileKind::Pe32 | FileKind::Pe64)) {
+            return Err(IconError::NotPeFile(file_path.to_path_buf()).into());
+        }
+        Resources::parse(data)
+    }
+
+    /// Every resource of type `kind`, in the first language each one is
+    /// available in, in directory order.
+    pub(crate) fn of_type(&self, kind: u16) -> Result<Vec<Resource<'data>>> {
+        self.collect(kind, 1)
+    }
+
+    /// Like `of_type`, but with one entry per language of each resource.
+    pub(crate) fn of_type_in_all_languages(&self, kind: u16) -> Result<Vec<Resource<'data>>> {
+        self.collect(kind, usize::MAX)
+    }
+
+    fn collect(&self, kind: u16, max_languages: usize) -> Result<Vec<Resource<'data>>> {
+        let root = self.directory.root()?;
+        let Some(names) = root
+            .entries
+            .iter()
+            .find(|entry| entry.name_or_id().id() == Some(kind))
+        else {
+            return Ok(Vec::new());
+        };
+        let names = names
+            .data(self.directory)?
+            .table()
+            .context("Malformed resource directory.")?;
+
+        let mut resources = Vec::new();
+        for name in names.entries {
+            let key = self.key(name)?;
+            let Some(languages) = name.data(self.directory)?.table() else {
+                continue;
+            };
+            for language in languages.entries.iter().take(max_languages) {
+                let Some(entry) = language.data(self.directory)?.data() else {
+                    continue;
+                };
+                resources.push(Resource {
+                    key: key.clone(),
+                    language: language.name_or_id().id().unwrap_or_default(),
+                    rva: entry.offset_to_data.get(object::LittleEndian),
+                    data: self.entry_data(entry)?,
+                });
+            }
+        }
+        Ok(resources)
+    }
+
+    /// The file offset the data at `rva` is stored at.
+    pub(crate) fn file_offset(&self, rva: u32) -> Option<u32> {
+        self.sections
+            .pe_file_range_at(rva)
+            .map(|(offset, _)| offset)
+    }
+
+    fn key(&self, entry: &ImageResourceDirectoryEntry) -> Result<ResourceKey> {
+        Ok(match entry.name_or_id() {
+            ResourceNameOrId::Id(id) => ResourceKey::Id(id),
+            ResourceNameOrId::Name(name) => {
+                ResourceKey::Name(name.to_string_lossy(self.directory)?)
+            }
+        })
+    }
+
+    fn entry_data(&self, entry: &ImageResourceDataEntry) -> Result<&'data [u8]> {
+        let size = entry.size.get(object::LittleEndian) as usize;
+        self.sections
+            .pe_data_at(self.data, entry.offset_to_data.get(object::LittleEndian))
+            .and_then(|data| data.get(..size))
+            .context("Resource data lies outside the file.")
+    }
+}
+
+fn parse_resources<Pe: ImageNtHeaders>(
+    data: &[u8],
+) -> object::Result<Option<(ResourceDirectory<'_>, SectionTable<'_>)>> {
+    let dos_header = ImageDosHeader::parse(data)?;
+    let mut offset = dos_header.nt_headers_offset().into();
+    let (nt_headers, data_directories) = Pe::parse(data, &mut offset)?;
+    let sections = nt_headers.sections(data, offset)?;
+    let directory = data_directories.resource_directory(data, &sections)?;
+    Ok(directory.map(|directory| (directory, sections)))
+}
+
+/// Minimal PE files for tests, built in memory.
+#[cfg(test)]
+pub(crate) mod fixture {
+    use super::ResourceKey;
+    use std::path::PathBuf;
+
+    /// Where the `.rsrc` section is loaded and stored.
+    pub(crate) const RSRC_RVA: u32 = 0x1000;
+    pub(crate) const RSRC_OFFSET: u32 = 0x200;
+
+    const SUBDIRECTORY: u32 = 0x8000_0000;
+
+    fn put_u16(buf: &mut [u8], at: usize, value: u16) {
+        buf[at..at + 2].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn put_u32(buf: &mut [u8], at: usize, value: u32) {
+        buf[at..at + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    /// An `IMAGE_RESOURCE_DIRECTORY` with `(name, target)` entries, named
+    /// ones (high bit set in `name`) first.
+    fn resource_table(entries: &[(u32, u32)]) -> Vec<u8> {
+        let named = entries
+            .iter()
+            .filter(|(name, _)| name & SUBDIRECTORY != 0)
+            .count();
+        let mut table = vec![0; 16];
+        put_u16(&mut table, 12, named as u16);
+        put_u16(&mut table, 14, (entries.len() - named) as u16);
+        for &(name, target) in entries {
+            table.extend_from_slice(&name.to_le_bytes());
+            table.extend_from_slice(&target.to_le_bytes());
+        }
+        table
+    }
+
+    /// A minimal 32-bit PE file whose only section holds a resource tree
+    /// with `resources` (type, ID, data; grouped by type) in language 1033,
+    /// or no resource directory at all when `resources` is empty.
+    pub(crate) fn build_pe(resources: &[(u16, u16, &[u8])]) -> Vec<u8> {
+        let keyed: Vec<_> = resources
+            .iter()
+            .map(|&(kind, id, data)| (kind, ResourceKey::Id(id), data))
+            .collect();
+        build_pe_with_keys(&keyed)
+    }
+
+    /// Like `build_pe`, with named resources too.
+    pub(crate) fn build_pe_with_keys(resources: &[(u16, ResourceKey, &[u8])]) -> Vec<u8> {
+        let section = resource_section(resources);
+
+        let mut pe = vec![0; RSRC_OFFSET as usize];
+        pe[..2].copy_from_slice(b"MZ");
+        put_u32(&mut pe, 0x3c, 0x40);
+        pe[0x40..0x44].copy_from_slice(b"PE\0\0");
+        // COFF header: i386, one section, a PE32 optional header.
+        let coff = 0x44;
+        put_u16(&mut pe, coff, 0x14c);
+        put_u16(&mut pe, coff + 2, 1);
+        put_u16(&mut pe, coff + 16, 224);
+        put_u16(&mut pe, coff + 18, 0x0102);
+        let optional = coff + 20;
+        put_u16(&mut pe, optional, 0x10b);
+        put_u32(&mut pe, optional + 28, 0x40_0000);
+        put_u32(&mut pe, optional + 32, 0x1000);
+        put_u32(&mut pe, optional + 36, 0x200);
+        put_u32(&mut pe, optional + 56, RSRC_RVA + section.len() as u32);
+        put_u32(&mut pe, optional + 60, RSRC_OFFSET);
+        put_u16(&mut pe, optional + 68, 2);
+        put_u32(&mut pe, optional + 92, 16);
+        if !resources.is_empty() {
+            put_u32(&mut pe, optional + 96 + 2 * 8, RSRC_RVA);
+            put_u32(&mut pe, optional + 96 + 2 * 8 + 4, section.len() as u32);
+        }
+        let header = optional + 224;
+        pe[header..header + 5].copy_from_slice(b".rsrc");
+        put_u32(&mut pe, header + 8, section.len() as u32);
+        put_u32(&mut pe, header + 12, RSRC_RVA);
+        put_u32(&mut pe, header + 16, section.len() as u32);
+        put_u32(&mut pe, header + 20, RSRC_OFFSET);
+        put_u32(&mut pe, header + 36, 0x4000_0040);
+
+        pe.extend(section);
+        pe
+    }
+
+    fn resource_section(resources: &[(u16, ResourceKey, &[u8])]) -> Vec<u8> {
+        let mut kinds: Vec<u16> = resources.iter().map(|r| r.0).collect();
+        kinds.dedup();
+        let of_kind = |kind: u16| {
+            resources
+                .iter()
+                .enumerate()
+                .filter(move |(_, r)| r.0 == kind)
+        };
+
+        // The root table, one name table per type, one language table per
+        // resource, the data entries, the name strings, then the data.
+        let mut offset = 16 + 8 * kinds.len();
+        let mut name_tables = Vec::new();
+        for &kind in &kinds {
+            name_tables.push(offset as u32);
+            offset += 16 + 8 * of_kind(kind).count();
+        }
+        let language_tables = offset;
+        offset += 24 * resources.len();
+        let data_entries = offset;
+        offset += 16 * resources.len();
+        let mut strings = Vec::new();
+        for (_, key, _) in resources {
+            strings.push(offset as u32);
+            if let ResourceKey::Name(name) = key {
+                offset += 2 + 2 * name.encode_utf16().count();
+            }
+        }
+        let mut data_offsets = Vec::new();
+        for (_, _, data) in resources {
+            offset = offset.next_multiple_of(4);
+            data_offsets.push(offset);
+            offset += data.len();
+        }
+
+        let root: Vec<(u32, u32)> = kinds
+            .iter()
+            .zip(&name_tables)
+            .map(|(&kind, &table)| (kind as u32, table | SUBDIRECTORY))
+            .collect();
+        let mut section = resource_table(&root);
+        for &kind in &kinds {
+            let entries: Vec<(u32, u32)> = of_kind(kind)
+                .map(|(i, (_, key, _))| {
+                    let name = match key {
+                        ResourceKey::Id(id) => *id as u32,
+                        ResourceKey::Name(_) => strings[i] | SUBDIRECTORY,
+                    };
+                    (name, (language_tables + 24 * i) as u32 | SUBDIRECTORY)
+                })
+                .collect();
+            section.extend(resource_table(&entries));
+        }
+        for i in 0..resources.len() {
+            section.extend(resource_table(&[(1033, (data_entries + 16 * i) as u32)]));
+        }
+        for ((_, _, data), &at) in resources.iter().zip(&data_offsets) {
+            let mut entry = vec![0; 16];
+            put_u32(&mut entry, 0, RSRC_RVA + at as u32);
+            put_u32(&mut entry, 4, data.len() as u32);
+            section.extend(entry);
+        }
+        for (_, key, _) in resources {
+            if let ResourceKey::Name(name) = key {
+                section.extend((name.encode_utf16().count() as u16).to_le_bytes());
+                section.extend(name.encode_utf16().flat_map(u16::to_le_bytes));
+            }
+        }
+        for ((_, _, data), &at) in resources.iter().zip(&data_offsets) {
+            section.resize(at, 0);
+            section.extend_from_slice(data);
+        }
+
+        section.resize(section.len().next_multiple_of(0x200).max(0x200), 0);
+        section
+    }
+
+    /// Writes `bytes` to a file named `name` in a new temporary directory.
+    pub(crate) fn write_temp(name: &str, bytes: &[u8]) -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(name);
+        std::fs::write(&path, bytes).unwrap();
+        (dir, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fixture::*;
+    use super::*;
+    use object::pe::RT_VERSION;
+
+    #[test]
+    fn contains_icon_finds_icon_resources() {
+        let (_dir, path) = write_temp(
+            "app.exe",
+            &build_pe(&[(RT_ICON, 1, b"icon"), (RT_GROUP_ICON, 1, b"group")]),
+        );
+        assert!(contains_icon(&path).unwrap());
+    }
+
+    #[test]
+    fn contains_icon_is_false_without_icons() {
+        let (_dir, path) = write_temp("version.dll", &build_pe(&[(RT_VERSION, 1, b"v1")]));
+        assert!(!contains_icon(&path).unwrap());
+
+        let (_dir, path) = write_temp("bare.exe", &build_pe(&[]));
+        assert!(!contains_icon(&path).unwrap());
+    }
+
+    #[test]
+    fn contains_icon_rejects_non_pe_files() {
+        for bytes in [&b"just some text"[..], b"MZ but nothing else", b""] {
+            let (_dir, path) = write_temp("notes.exe", bytes);
+            let err = contains_icon(&path).unwrap_err();
+            assert!(
+                matches!(err.downcast_ref(), Some(IconError::NotPeFile(p)) if *p == path),
+                "unexpected error: {err:#}"
+            );
+        }
+    }
+
+    #[test]
+    fn file_backed_parsers_reject_non_pe_files() {
+        // An `MZ` header passes the extension and magic checks, but there is
+        // no PE image behind it.
+        let mut dos = b"MZ".to_vec();
+        dos.resize(256, 0);
+        let (dir, path) = write_temp("old.exe", &dos);
+        let ico = crate::icon_group::build_ico(&[crate::icon_group::IconImage {
+            fields: &[16, 16, 0, 0, 1, 0, 32, 0],
+            data: b"image",
+        }]);
+
+        let results = [
+            crate::extract_all_icon_groups_as_icos(&path, dir.path()).map(drop),
+            crate::icon_group::native_sizes(&path, 0).map(drop),
+            crate::verify_icon_resources(&path).map(drop),
+            crate::IconIterator::new(&path).map(drop),
+            crate::strip_icon_from_exe(&path),
+            crate::replace_icon_in_exe(&path, &ico, 0),
+            crate::copy_icon_between_executables(&path, 0, &path, 0),
+        ];
+        for result in results {
+            let err = result.unwrap_err();
+            assert!(
+                matches!(err.downcast_ref(), Some(IconError::NotPeFile(_))),
+                "unexpected error: {err:#}"
+            );
+        }
+    }
+
+    #[test]
+    fn locate_icon_resource_points_at_the_image() {
+        let pe = build_pe(&[
+            (RT_ICON, 1, b"first image"),
+            (RT_ICON, 2, b"second"),
+            (RT_GROUP_ICON, 1, b"group"),
+        ]);
+        let (_dir, path) = write_temp("app.exe", &pe);
+
+        let location = locate_icon_resource(&path, 1).unwrap();
+        let start = location.file_offset as usize;
+        assert_eq!(&pe[start..start + location.size as usize], b"second");
+        assert_eq!(
+            location.rva - RSRC_RVA,
+            location.file_offset as u32 - RSRC_OFFSET
+        );
+
+        let err = locate_icon_resource(&path, 2).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(IconError::NoIcon { count: Some(2), .. })
+        ));
+    }
+}
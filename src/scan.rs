@@ -0,0 +1,97 @@
+use crate::ico_file::{decode_ico, untrusted_limits};
+use crate::icon_group::parse_ico;
+use anyhow::Result;
+use image::{ImageFormat, ImageReader, RgbaImage};
+use std::io::Cursor;
+use std::path::Path;
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+/// An ICO header: reserved 0, type 1, then the image count.
+const ICO_SIGNATURE: &[u8] = &[0, 0, 1, 0];
+/// Well-formed candidates decoded before giving up, so large files with many
+/// plausible matches stay fast. Signature hits whose headers don't hold up
+/// are skipped without counting towards it.
+const MAX_CANDIDATES: usize = 64;
+
+/// Last-resort search for an embedded icon when the file has no icon
+/// resources, e.g. self-extracting archives that keep their images in the
+/// overlay. Returns the largest PNG or ICO image found anywhere in the file.
+pub(crate) fn scan_for_icon(file_path: &Path) -> Result<Option<RgbaImage>> {
+    let data = std::fs::read(file_path)?;
+
+    let best = candidates(&data)
+        .take(MAX_CANDIDATES)
+        .filter_map(|(offset, format)| decode_at(&data[offset..], format))
+        .max_by_key(|img| img.width() as u64 * img.height() as u64);
+
+    Ok(best)
+}
+
+fn candidates(data: &[u8]) -> impl Iterator<Item = (usize, ImageFormat)> + '_ {
+    (0..data.len()).filter_map(|offset| {
+        let rest = &data[offset..];
+        // A PNG must open with its IHDR chunk.
+        if rest.starts_with(PNG_SIGNATURE) && rest.get(8..16) == Some(b"\0\0\0\rIHDR") {
+            return Some((offset, ImageFormat::Png));
+        }
+        // A plausible count keeps runs of zeros from matching everywhere, and
+        // the directory has to point at data inside the file.
+        let count = rest.get(4..6).map(|b| u16::from_le_bytes([b[0], b[1]]));
+        if rest.starts_with(ICO_SIGNATURE)
+            && matches!(count, Some(1..=32))
+            && parse_ico(rest).is_ok()
+        {
+            return Some((offset, ImageFormat::Ico));
+        }
+        None
+    })
+}
+
+fn decode_at(data: &[u8], format: ImageFormat) -> Option<RgbaImage> {
+    match format {
+        ImageFormat::Ico => decode_ico(data)
+            .ok()?
+            .into_iter()
+            .max_by_key(|img| img.width() as u64 * img.height() as u64),
+        _ => {
+            let mut reader = ImageReader::with_format(Cursor::new(data), format);
+            reader.limits(untrusted_limits());
+            Some(reader.decode().ok()?.to_rgba8())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pe::fixture::write_temp;
+
+    fn png(size: u32) -> Vec<u8> {
+        let mut png = Vec::new();
+        RgbaImage::new(size, size)
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        png
+    }
+
+    #[test]
+    fn broken_candidates_dont_use_up_the_limit() {
+        // ICO headers whose single entry points far past the end of the file.
+        let mut fake_ico = vec![0, 0, 1, 0, 1, 0, 16, 16, 0, 0, 1, 0, 32, 0];
+        fake_ico.extend_from_slice(&0x100u32.to_le_bytes());
+        fake_ico.extend_from_slice(&0x00FF_FFFFu32.to_le_bytes());
+
+        let mut data = b"MZ".to_vec();
+        for _ in 0..MAX_CANDIDATES * 2 {
+            data.extend_from_slice(&fake_ico);
+            data.extend_from_slice(PNG_SIGNATURE);
+        }
+        data.extend_from_slice(&png(24));
+        let (_dir, path) = write_temp("setup.exe", &data);
+
+        let img = scan_for_icon(&path)
+            .unwrap()
+            .expect("the real PNG is found");
+        assert_eq!(img.dimensions(), (24, 24));
+    }
+}
@@ -0,0 +1,167 @@
+use crate::extract_icon_image;
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+#[cfg(windows)]
+use std::ptr::null_mut;
+#[cfg(windows)]
+use widestring::{U16CStr, U16CString};
+#[cfg(windows)]
+use winapi::um::processenv::ExpandEnvironmentStringsW;
+#[cfg(windows)]
+use winapi::um::winsvc::{
+    CloseServiceHandle, OpenSCManagerW, OpenServiceW, QUERY_SERVICE_CONFIGW, QueryServiceConfigW,
+    SC_HANDLE, SC_MANAGER_CONNECT, SERVICE_QUERY_CONFIG,
+};
+
+/// Extracts the icon of the executable behind a Windows service and saves it
+/// as `<service_name>.png` in `out_dir`.
+pub fn extract_service_icon(service_name: &str, out_dir: &Path) -> Result<PathBuf> {
+    let command_line = service_command_line(service_name)?;
+    let exe_path = executable_from_command_line(&command_line);
+
+    let img = extract_icon_image(&exe_path, 0)
+        .with_context(|| format!("Failed to extract icon for service: {service_name}"))?;
+
+    let output_path = out_dir.join(format!("{service_name}.png"));
+    img.save(&output_path)?;
+
+    Ok(output_path)
+}
+
+/// The service's binary path with environment variables expanded.
+#[cfg(windows)]
+fn service_command_line(service_name: &str) -> Result<String> {
+    expand_environment(&service_binary_path(service_name)?)
+}
+
+#[cfg(not(windows))]
+fn service_command_line(_service_name: &str) -> Result<String> {
+    Err(crate::IconError::UnsupportedPlatform.into())
+}
+
+/// Closes the wrapped SCM handle on drop.
+#[cfg(windows)]
+struct ServiceHandle(SC_HANDLE);
+
+#[cfg(windows)]
+impl Drop for ServiceHandle {
+    fn drop(&mut self) {
+        unsafe {
+            CloseServiceHandle(self.0);
+        }
+    }
+}
+
+#[cfg(windows)]
+fn service_binary_path(service_name: &str) -> Result<String> {
+    let name = U16CString::from_str(service_name)?;
+
+    unsafe {
+        let manager = OpenSCManagerW(null_mut(), null_mut(), SC_MANAGER_CONNECT);
+        if manager.is_null() {
+            anyhow::bail!("OpenSCManagerW failed.");
+        }
+        let manager = ServiceHandle(manager);
+
+        let service = OpenServiceW(manager.0, name.as_ptr(), SERVICE_QUERY_CONFIG);
+        if service.is_null() {
+            anyhow::bail!("OpenServiceW failed for service: {service_name}");
+        }
+        let service = ServiceHandle(service);
+
+        let mut needed = 0;
+        QueryServiceConfigW(service.0, null_mut(), 0, &mut needed);
+        if needed == 0 {
+            anyhow::bail!("QueryServiceConfigW failed for service: {service_name}");
+        }
+
+        // u64 storage keeps the struct at the head of the buffer aligned.
+        let mut buf = vec![0u64; (needed as usize).div_ceil(8)];
+        let config = buf.as_mut_ptr() as *mut QUERY_SERVICE_CONFIGW;
+        if QueryServiceConfigW(service.0, config, needed, &mut needed) == 0 {
+            anyhow::bail!("QueryServiceConfigW failed for service: {service_name}");
+        }
+
+        let binary_path = (*config).lpBinaryPathName;
+        if binary_path.is_null() {
+            anyhow::bail!("Service has no binary path: {service_name}");
+        }
+        Ok(U16CStr::from_ptr_str(binary_path).to_string_lossy())
+    }
+}
+
+#[cfg(windows)]
+fn expand_environment(s: &str) -> Result<String> {
+    let src = U16CString::from_str(s)?;
+
+    unsafe {
+        let len = ExpandEnvironmentStringsW(src.as_ptr(), null_mut(), 0);
+        if len == 0 {
+            anyhow::bail!("ExpandEnvironmentStringsW failed.");
+        }
+        let mut buf = vec![0u16; len as usize];
+        if ExpandEnvironmentStringsW(src.as_ptr(), buf.as_mut_ptr(), len) == 0 {
+            anyhow::bail!("ExpandEnvironmentStringsW failed.");
+        }
+        Ok(U16CStr::from_slice_truncate(&buf)?.to_string_lossy())
+    }
+}
+
+/// Picks the executable out of a service command line such as
+/// `"C:\Program Files\App\svc.exe" --flag` or `C:\Windows\system32\svchost.exe -k netsvcs`.
+fn executable_from_command_line(command_line: &str) -> PathBuf {
+    let command_line = command_line.trim();
+
+    if let Some(quoted) = command_line.strip_prefix('"') {
+        let path = quoted.split_once('"').map_or(quoted, |(path, _)| path);
+        return PathBuf::from(path);
+    }
+
+    // Unquoted paths may contain spaces, so take the shortest prefix ending at
+    // a space that names an existing file, the same way CreateProcess does.
+    command_line
+        .match_indices(' ')
+        .map(|(i, _)| &command_line[..i])
+        .chain(Some(command_line))
+        .map(PathBuf::from)
+        .find(|candidate| candidate.is_file())
+        .unwrap_or_else(|| PathBuf::from(command_line))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quoted_path_ends_at_the_closing_quote() {
+        assert_eq!(
+            executable_from_command_line(r#""C:\Program Files\App\svc.exe" --flag "x y""#),
+            PathBuf::from(r"C:\Program Files\App\svc.exe")
+        );
+        assert_eq!(
+            executable_from_command_line(r#"  "C:\App\svc.exe""#),
+            PathBuf::from(r"C:\App\svc.exe")
+        );
+    }
+
+    #[test]
+    fn unquoted_path_ends_at_the_first_existing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("My App")).unwrap();
+        let exe = dir.path().join("My App").join("svc.exe");
+        std::fs::write(&exe, b"").unwrap();
+
+        let command_line = format!("{} -k netsvcs", exe.display());
+        assert_eq!(executable_from_command_line(&command_line), exe);
+        let command_line = exe.display().to_string();
+        assert_eq!(executable_from_command_line(&command_line), exe);
+    }
+
+    #[test]
+    fn unquoted_path_to_missing_file_is_kept_whole() {
+        assert_eq!(
+            executable_from_command_line(r"C:\Missing\svc.exe -k netsvcs"),
+            PathBuf::from(r"C:\Missing\svc.exe -k netsvcs")
+        );
+    }
+}
@@ -0,0 +1,175 @@
+use anyhow::{Context, Result};
+use icon_extractor::{
+    ExtractionConfig, extract_icon_image, extract_icon_with_metadata, icon_count,
+};
+use rustyline::DefaultEditor;
+use rustyline::error::ReadlineError;
+use std::env;
+use std::path::{Path, PathBuf};
+
+const HELP: &str = "Commands:
+  load <path>                    Open a file for the following commands
+  list                           List the icons in the loaded file
+  info <index>                   Show details of one icon
+  extract <index> [--output <f>] Save one icon (default: icon_<index>.png)
+  help                           Show this help
+  quit                           Leave the shell";
+
+/// The file picked with `load`, kept across commands.
+struct Loaded {
+    path: PathBuf,
+    count: u32,
+}
+
+pub fn run() -> Result<()> {
+    let mut editor = DefaultEditor::new()?;
+    let mut loaded: Option<Loaded> = None;
+
+    println!("icon_extractor shell. Type `help` for commands.");
+
+    loop {
+        let line = match editor.readline("> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted | ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        _ = editor.add_history_entry(line.as_str());
+
+        let words = split_words(&line);
+        // A line like `""` has no words even though it isn't blank.
+        let Some((command, args)) = words.split_first() else {
+            continue;
+        };
+        let result = match command.as_str() {
+            "load" => load(args).map(|l| loaded = Some(l)),
+            "list" => with_loaded(&loaded, list),
+            "info" => with_loaded(&loaded, |l| info(l, args)),
+            "extract" => with_loaded(&loaded, |l| extract(l, args)),
+            "help" => {
+                println!("{HELP}");
+                Ok(())
+            }
+            "quit" | "exit" => break,
+            _ => Err(anyhow::anyhow!("Unknown command: {command}. Type `help`.")),
+        };
+
+        if let Err(e) = result {
+            eprintln!("Error: {e:#}");
+        }
+    }
+
+    Ok(())
+}
+
+fn with_loaded(loaded: &Option<Loaded>, f: impl FnOnce(&Loaded) -> Result<()>) -> Result<()> {
+    let loaded = loaded
+        .as_ref()
+        .context("No file loaded. Use `load <path>` first.")?;
+    f(loaded)
+}
+
+fn load(args: &[String]) -> Result<Loaded> {
+    let [path] = args else {
+        anyhow::bail!("Usage: load <path>");
+    };
+    let path = resolve(Path::new(path));
+    let count = icon_count(&path)?;
+    println!("Loaded {} ({count} icons)", path.display());
+    Ok(Loaded { path, count })
+}
+
+/// Bare file names such as `shell32.dll` are looked up in System32 when they
+/// don't exist relative to the working directory.
+fn resolve(path: &Path) -> PathBuf {
+    if path.exists() || path.components().count() > 1 {
+        return path.to_path_buf();
+    }
+    env::var_os("SystemRoot")
+        .map(|root| Path::new(&root).join("System32").join(path))
+        .filter(|candidate| candidate.exists())
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+fn list(loaded: &Loaded) -> Result<()> {
+    for index in 0..loaded.count {
+        match extract_icon_image(&loaded.path, index) {
+            Ok(img) => println!("{index}: {}x{}", img.width(), img.height()),
+            Err(e) => println!("{index}: {e}"),
+        }
+    }
+    Ok(())
+}
+
+fn info(loaded: &Loaded, args: &[String]) -> Result<()> {
+    let [index] = args else {
+        anyhow::bail!("Usage: info <index>");
+    };
+    let index = parse_index(loaded, index)?;
+    let (_, metadata) =
+        extract_icon_with_metadata(&loaded.path, index, &ExtractionConfig::default())?;
+    println!("File:     {}", loaded.path.display());
+    println!("Index:    {index} of {}", loaded.count);
+    println!("Size:     {}x{}", metadata.width, metadata.height);
+    println!("Coverage: {:.0}%", metadata.alpha_coverage * 100.0);
+    for warning in &metadata.warnings {
+        println!("Warning:  {warning}");
+    }
+    Ok(())
+}
+
+fn extract(loaded: &Loaded, args: &[String]) -> Result<()> {
+    let (index, output) = match args {
+        [index] => (index, None),
+        [index, flag, output] if flag == "--output" || flag == "-o" => (index, Some(output)),
+        _ => anyhow::bail!("Usage: extract <index> [--output <file>]"),
+    };
+    let index = parse_index(loaded, index)?;
+    let output_path = output
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(format!("icon_{index}.png")));
+
+    extract_icon_image(&loaded.path, index)?.save(&output_path)?;
+    println!("Icon extracted to: {}", output_path.display());
+    Ok(())
+}
+
+fn parse_index(loaded: &Loaded, index: &str) -> Result<u32> {
+    let index: u32 = index
+        .parse()
+        .with_context(|| format!("Invalid icon index: {index}"))?;
+    if index >= loaded.count {
+        anyhow::bail!(
+            "No icon at index {index}; {} has {} icons",
+            loaded.path.display(),
+            loaded.count
+        );
+    }
+    Ok(index)
+}
+
+/// Splits a command line on whitespace, keeping `"quoted parts"` together.
+fn split_words(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+
+    for c in line.chars() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            c if c.is_whitespace() && !in_quotes => {
+                if !current.is_empty() {
+                    words.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+
+    words
+}
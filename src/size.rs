@@ -0,0 +1,320 @@
+use crate::icon_group::native_sizes;
+use crate::{IconMetadata, IconWarning};
+#[cfg(windows)]
+use crate::{check_icon_file, gdi, to_extended_path};
+use anyhow::Result;
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, RgbaImage};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+#[cfg(windows)]
+use winapi::shared::windef::HWND;
+#[cfg(windows)]
+use winapi::um::winuser::{DestroyIcon, GetDpiForWindow};
+#[cfg(windows)]
+use windows::Win32::UI::Shell::SHDefExtractIconW;
+#[cfg(windows)]
+use windows::Win32::UI::WindowsAndMessaging::HICON;
+#[cfg(windows)]
+use windows::core::PCWSTR;
+
+/// Renders the icon at `index` at `size`x`size` pixels. The shell picks the
+/// closest image in the icon group and scales it when no exact match exists.
+#[cfg(windows)]
+pub fn extract_icon_at_size(file_path: &Path, index: u32, size: u32) -> Result<RgbaImage> {
+    let ordinal = crate::ordinal(index)?;
+    let file_str = to_extended_path(&check_icon_file(file_path)?);
+
+    unsafe {
+        let mut hicon = HICON::default();
+        let hr = SHDefExtractIconW(
+            PCWSTR(file_str.as_ptr()),
+            ordinal,
+            0,
+            Some(&mut hicon),
+            None,
+            size & 0xFFFF, // LOWORD is the large icon size
+        );
+        // S_FALSE (no icon) is a success code, so check the handle too.
+        if hr.is_err() || hicon.is_invalid() {
+            return Err(crate::IconError::NoIcon {
+                path: file_path.to_path_buf(),
+                index: ordinal,
+                count: crate::icon_count(file_path).ok(),
+            }
+            .into());
+        }
+
+        let hicon = hicon.0 as _;
+        let img = gdi::hicon_to_image(hicon);
+        DestroyIcon(hicon);
+        img
+    }
+}
+
+/// Renders the icon at `index` at `size`x`size` pixels.
+/// Always fails with [`crate::IconError::UnsupportedPlatform`] off Windows.
+#[cfg(not(windows))]
+pub fn extract_icon_at_size(file_path: &Path, _index: u32, _size: u32) -> Result<RgbaImage> {
+    crate::check_icon_file(file_path)?;
+    Err(crate::IconError::UnsupportedPlatform.into())
+}
+
+/// Extracts the first icon at every size its icon group holds natively,
+/// keyed by size. Only sizes with an image of their own are included, so no
+/// entry is a scaled copy of another.
+pub fn extract_icons_by_size(file_path: &Path) -> Result<BTreeMap<u32, RgbaImage>> {
+    native_sizes(file_path, 0)?
+        .into_iter()
+        .map(|size| Ok((size, extract_icon_at_size(file_path, 0, size)?)))
+        .collect()
+}
+
+/// Extracts the first icon at the large-icon size for `hwnd`'s current DPI
+/// (32px at 96 DPI, scaled proportionally) and saves it as `icon_<size>.png`
+/// in `out_dir`.
+///
+/// `hwnd` is only used to query its DPI. A stale or invalid handle is not
+/// undefined behavior; it makes `GetDpiForWindow` fail, which is reported as
+/// an error.
+#[cfg(windows)]
+// HWND is an opaque handle that user32 validates, never a pointer we read.
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub fn extract_icon_for_window(file_path: &Path, hwnd: HWND, out_dir: &Path) -> Result<PathBuf> {
+    let dpi = unsafe { GetDpiForWindow(hwnd) };
+    if dpi == 0 {
+        anyhow::bail!("GetDpiForWindow failed; is the window handle valid?");
+    }
+
+    let size = 32 * dpi / 96;
+    let img = extract_icon_at_size(file_path, 0, size)?;
+
+    let output_path = out_dir.join(format!("icon_{size}.png"));
+    img.save(&output_path)?;
+
+    Ok(output_path)
+}
+
+/// Saves the first icon at exactly `target`x`target` pixels as
+/// `icon_<target>.png` in `out_dir`, for the sharpest result at sizes the
+/// file doesn't carry. The smallest native image at least as large is
+/// downscaled with Lanczos; the largest one is upscaled (with a warning)
+/// only when every native image is smaller.
+pub fn extract_icon_quality(file_path: &Path, target: u32, out_dir: &Path) -> Result<PathBuf> {
+    let img = render_from_native(file_path, target)?;
+
+    let output_path = out_dir.join(format!("icon_{target}.png"));
+    img.save(&output_path)?;
+
+    Ok(output_path)
+}
+
+/// The first icon at `target`x`target`, scaled from the best native image as
+/// described on `extract_icon_quality`.
+pub(crate) fn render_from_native(file_path: &Path, target: u32) -> Result<RgbaImage> {
+    let source = source_size(&native_sizes(file_path, 0)?, target);
+
+    let mut img = extract_icon_at_size(file_path, 0, source)?;
+    if img.width() != target || img.height() != target {
+        if img.width() < target || img.height() < target {
+            let warning = IconWarning::Upscaled {
+                from: img.width().max(img.height()),
+                to: target,
+            };
+            tracing::warn!("Icon of {}: {warning}", file_path.display());
+        }
+        img = imageops::resize(&img, target, target, FilterType::Lanczos3);
+    }
+    Ok(img)
+}
+
+/// The native size to render `target` from: the smallest of `sizes` at least
+/// as large, else the largest. With no square sizes at all, `target` itself,
+/// leaving the choice to the shell.
+fn source_size(sizes: &[u32], target: u32) -> u32 {
+    sizes
+        .iter()
+        .find(|&&size| size >= target)
+        .or(sizes.last())
+        .copied()
+        .unwrap_or(target)
+}
+
+/// Extracts the icon at `index` scaled to exactly `size`x`size` pixels,
+/// whatever sizes the file has. Larger icons are downscaled with Lanczos;
+/// smaller ones are enlarged with nearest-neighbor so they stay crisp.
+pub fn extract_icon_thumbnail(file_path: &Path, index: u32, size: u32) -> Result<DynamicImage> {
+    extract_icon_thumbnail_with_metadata(file_path, index, size).map(|(img, _)| img)
+}
+
+/// Scales `img` to exactly `size`x`size` as `extract_icon_thumbnail`
+/// describes, adding [`IconWarning::Upscaled`] when it has to be enlarged.
+fn fit_to(img: RgbaImage, warnings: &mut Vec<IconWarning>, size: u32) -> RgbaImage {
+    if img.width() == size && img.height() == size {
+        return img;
+    }
+    let filter = if img.width() > size || img.height() > size {
+        FilterType::Lanczos3
+    } else {
+        warnings.push(IconWarning::Upscaled {
+            from: img.width().max(img.height()),
+            to: size,
+        });
+        FilterType::Nearest
+    };
+    imageops::resize(&img, size, size, filter)
+}
+
+/// Like `extract_icon_thumbnail`, along with the thumbnail's
+/// [`IconMetadata`]; enlarging a smaller icon adds [`IconWarning::Upscaled`].
+pub fn extract_icon_thumbnail_with_metadata(
+    file_path: &Path,
+    index: u32,
+    size: u32,
+) -> Result<(DynamicImage, IconMetadata)> {
+    let (img, mut warnings) =
+        crate::extract_large_icon_with_warnings(file_path, crate::ordinal(index)?)?;
+    let img = fit_to(img, &mut warnings, size);
+
+    let metadata = IconMetadata {
+        warnings,
+        ..IconMetadata::from_image(&img)
+    };
+    Ok((img.into(), metadata))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn source_size_prefers_the_next_larger_size() {
+        assert_eq!(source_size(&[32, 48], 40), 48);
+        assert_eq!(source_size(&[16, 32, 48], 32), 32);
+    }
+
+    /// A checkerboard of black and white `cell`px squares.
+    fn checkerboard(size: u32, cell: u32) -> RgbaImage {
+        RgbaImage::from_fn(size, size, |x, y| {
+            let v = if (x / cell + y / cell).is_multiple_of(2) {
+                0
+            } else {
+                255
+            };
+            image::Rgba([v, v, v, 255])
+        })
+    }
+
+    #[test]
+    fn fit_to_upscales_crisply_with_a_warning() {
+        let mut warnings = Vec::new();
+        let img = fit_to(checkerboard(16, 1), &mut warnings, 64);
+
+        assert_eq!(img.dimensions(), (64, 64));
+        assert_eq!(warnings, [IconWarning::Upscaled { from: 16, to: 64 }]);
+        // Nearest-neighbor turns each source pixel into a solid 4x4 block.
+        assert_eq!(img, checkerboard(64, 4));
+    }
+
+    #[test]
+    fn fit_to_downscales_without_a_warning() {
+        let mut warnings = Vec::new();
+        assert_eq!(
+            fit_to(checkerboard(64, 4), &mut warnings, 16).dimensions(),
+            (16, 16)
+        );
+        assert_eq!(
+            fit_to(checkerboard(16, 4), &mut warnings, 16).dimensions(),
+            (16, 16)
+        );
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn source_size_falls_back_to_the_largest_or_target() {
+        assert_eq!(source_size(&[16, 32], 64), 32);
+        assert_eq!(source_size(&[], 64), 64);
+    }
+
+    /// A hidden, DPI-unaware window, which always reports 96 DPI.
+    #[cfg(windows)]
+    fn window_at_96_dpi() -> HWND {
+        use std::ptr::null_mut;
+        use winapi::shared::windef::DPI_AWARENESS_CONTEXT_UNAWARE;
+        use winapi::um::winuser::{CreateWindowExW, SetThreadDpiAwarenessContext};
+
+        let class: Vec<u16> = "STATIC\0".encode_utf16().collect();
+        unsafe {
+            let previous = SetThreadDpiAwarenessContext(DPI_AWARENESS_CONTEXT_UNAWARE);
+            let hwnd = CreateWindowExW(
+                0,
+                class.as_ptr(),
+                null_mut(),
+                0,
+                0,
+                0,
+                100,
+                100,
+                null_mut(),
+                null_mut(),
+                null_mut(),
+                null_mut(),
+            );
+            SetThreadDpiAwarenessContext(previous);
+            assert!(!hwnd.is_null(), "CreateWindowExW failed");
+            hwnd
+        }
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn window_icon_uses_the_windows_dpi() {
+        use image::GenericImageView;
+        use winapi::um::winuser::DestroyWindow;
+
+        let root = std::env::var_os("SystemRoot").expect("SystemRoot is set");
+        let shell32 = Path::new(&root).join(r"System32\shell32.dll");
+        let out_dir = tempfile::tempdir().unwrap();
+
+        let hwnd = window_at_96_dpi();
+        let result = extract_icon_for_window(&shell32, hwnd, out_dir.path());
+        unsafe { DestroyWindow(hwnd) };
+
+        let path = result.unwrap();
+        assert_eq!(path, out_dir.path().join("icon_32.png"));
+        assert_eq!(image::open(&path).unwrap().dimensions(), (32, 32));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn window_icon_rejects_invalid_handles() {
+        let root = std::env::var_os("SystemRoot").expect("SystemRoot is set");
+        let shell32 = Path::new(&root).join(r"System32\shell32.dll");
+        let out_dir = tempfile::tempdir().unwrap();
+
+        let err =
+            extract_icon_for_window(&shell32, std::ptr::null_mut(), out_dir.path()).unwrap_err();
+        assert!(
+            err.to_string().contains("GetDpiForWindow failed"),
+            "{err:#}"
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn icons_by_size_match_their_keys() {
+        let root = std::env::var_os("SystemRoot").expect("SystemRoot is set");
+        let shell32 = Path::new(&root).join(r"System32\shell32.dll");
+
+        let icons = extract_icons_by_size(&shell32).unwrap();
+
+        assert!(icons.len() > 1, "{:?}", icons.keys());
+        assert_eq!(
+            icons.keys().copied().collect::<Vec<_>>(),
+            native_sizes(&shell32, 0).unwrap()
+        );
+        for (&size, img) in &icons {
+            assert_eq!(img.dimensions(), (size, size));
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use crate::IconError;
+use anyhow::Result;
+use image::RgbaImage;
+use std::path::{Path, PathBuf};
+#[cfg(windows)]
+use winapi::um::shellapi::*;
+#[cfg(windows)]
+use winapi::um::winuser::DestroyIcon;
+
+macro_rules! stock_icons {
+    ($($variant:ident = $siid:ident, $name:literal;)*) => {
+        /// The shell's stock icons (`SIID_*`).
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        #[non_exhaustive]
+        pub enum StockIcon {
+            $(#[doc = concat!("`", stringify!($siid), "`")] $variant,)*
+        }
+
+        impl StockIcon {
+            /// Every stock icon, in `SIID_*` order.
+            pub const ALL: &[StockIcon] = &[$(StockIcon::$variant,)*];
+
+            /// A `snake_case` name for the icon, used as its file stem.
+            #[must_use]
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(StockIcon::$variant => $name,)*
+                }
+            }
+
+            #[cfg(windows)]
+            fn siid(self) -> SHSTOCKICONID {
+                match self {
+                    $(StockIcon::$variant => $siid,)*
+                }
+            }
+        }
+    };
+}
+
+stock_icons! {
+    DocNoAssoc = SIID_DOCNOASSOC, "doc_no_assoc";
+    DocAssoc = SIID_DOCASSOC, "doc_assoc";
+    Application = SIID_APPLICATION, "application";
+    Folder = SIID_FOLDER, "folder";
+    FolderOpen = SIID_FOLDEROPEN, "folder_open";
+    Drive525 = SIID_DRIVE525, "drive_525";
+    Drive35 = SIID_DRIVE35, "drive_35";
+    DriveRemove = SIID_DRIVEREMOVE, "drive_remove";
+    DriveFixed = SIID_DRIVEFIXED, "drive_fixed";
+    DriveNet = SIID_DRIVENET, "drive_net";
+    DriveNetDisabled = SIID_DRIVENETDISABLED, "drive_net_disabled";
+    DriveCd = SIID_DRIVECD, "drive_cd";
+    DriveRam = SIID_DRIVERAM, "drive_ram";
+    World = SIID_WORLD, "world";
+    Server = SIID_SERVER, "server";
+    Printer = SIID_PRINTER, "printer";
+    MyNetwork = SIID_MYNETWORK, "my_network";
+    Find = SIID_FIND, "find";
+    Help = SIID_HELP, "help";
+    Share = SIID_SHARE, "share";
+    Link = SIID_LINK, "link";
+    SlowFile = SIID_SLOWFILE, "slow_file";
+    Recycler = SIID_RECYCLER, "recycler";
+    RecyclerFull = SIID_RECYCLERFULL, "recycler_full";
+    MediaCdAudio = SIID_MEDIACDAUDIO, "media_cd_audio";
+    Lock = SIID_LOCK, "lock";
+    AutoList = SIID_AUTOLIST, "auto_list";
+    PrinterNet = SIID_PRINTERNET, "printer_net";
+    ServerShare = SIID_SERVERSHARE, "server_share";
+    PrinterFax = SIID_PRINTERFAX, "printer_fax";
+    PrinterFaxNet = SIID_PRINTERFAXNET, "printer_fax_net";
+    PrinterFile = SIID_PRINTERFILE, "printer_file";
+    Stack = SIID_STACK, "stack";
+    MediaSvcd = SIID_MEDIASVCD, "media_svcd";
+    StuffedFolder = SIID_STUFFEDFOLDER, "stuffed_folder";
+    DriveUnknown = SIID_DRIVEUNKNOWN, "drive_unknown";
+    DriveDvd = SIID_DRIVEDVD, "drive_dvd";
+    MediaDvd = SIID_MEDIADVD, "media_dvd";
+    MediaDvdRam = SIID_MEDIADVDRAM, "media_dvd_ram";
+    MediaDvdRw = SIID_MEDIADVDRW, "media_dvd_rw";
+    MediaDvdR = SIID_MEDIADVDR, "media_dvd_r";
+    MediaDvdRom = SIID_MEDIADVDROM, "media_dvd_rom";
+    MediaCdAudioPlus = SIID_MEDIACDAUDIOPLUS, "media_cd_audio_plus";
+    MediaCdRw = SIID_MEDIACDRW, "media_cd_rw";
+    MediaCdR = SIID_MEDIACDR, "media_cd_r";
+    MediaCdBurn = SIID_MEDIACDBURN, "media_cd_burn";
+    MediaBlankCd = SIID_MEDIABLANKCD, "media_blank_cd";
+    MediaCdRom = SIID_MEDIACDROM, "media_cd_rom";
+    AudioFiles = SIID_AUDIOFILES, "audio_files";
+    ImageFiles = SIID_IMAGEFILES, "image_files";
+    VideoFiles = SIID_VIDEOFILES, "video_files";
+    MixedFiles = SIID_MIXEDFILES, "mixed_files";
+    FolderBack = SIID_FOLDERBACK, "folder_back";
+    FolderFront = SIID_FOLDERFRONT, "folder_front";
+    Shield = SIID_SHIELD, "shield";
+    Warning = SIID_WARNING, "warning";
+    Info = SIID_INFO, "info";
+    Error = SIID_ERROR, "error";
+    Key = SIID_KEY, "key";
+    Software = SIID_SOFTWARE, "software";
+    Rename = SIID_RENAME, "rename";
+    Delete = SIID_DELETE, "delete";
+    MediaAudioDvd = SIID_MEDIAAUDIODVD, "media_audio_dvd";
+    MediaMovieDvd = SIID_MEDIAMOVIEDVD, "media_movie_dvd";
+    MediaEnhancedCd = SIID_MEDIAENHANCEDCD, "media_enhanced_cd";
+    MediaEnhancedDvd = SIID_MEDIAENHANCEDDVD, "media_enhanced_dvd";
+    MediaHdDvd = SIID_MEDIAHDDVD, "media_hd_dvd";
+    MediaBluRay = SIID_MEDIABLURAY, "media_blu_ray";
+    MediaVcd = SIID_MEDIAVCD, "media_vcd";
+    MediaDvdPlusR = SIID_MEDIADVDPLUSR, "media_dvd_plus_r";
+    MediaDvdPlusRw = SIID_MEDIADVDPLUSRW, "media_dvd_plus_rw";
+    DesktopPc = SIID_DESKTOPPC, "desktop_pc";
+    MobilePc = SIID_MOBILEPC, "mobile_pc";
+    Users = SIID_USERS, "users";
+    MediaSmartMedia = SIID_MEDIASMARTMEDIA, "media_smart_media";
+    MediaCompactFlash = SIID_MEDIACOMPACTFLASH, "media_compact_flash";
+    DeviceCellPhone = SIID_DEVICECELLPHONE, "device_cell_phone";
+    DeviceCamera = SIID_DEVICECAMERA, "device_camera";
+    DeviceVideoCamera = SIID_DEVICEVIDEOCAMERA, "device_video_camera";
+    DeviceAudioPlayer = SIID_DEVICEAUDIOPLAYER, "device_audio_player";
+    NetworkConnect = SIID_NETWORKCONNECT, "network_connect";
+    Internet = SIID_INTERNET, "internet";
+    ZipFile = SIID_ZIPFILE, "zip_file";
+    Settings = SIID_SETTINGS, "settings";
+    DriveHdDvd = SIID_DRIVEHDDVD, "drive_hd_dvd";
+    DriveBd = SIID_DRIVEBD, "drive_bd";
+    MediaHdDvdRom = SIID_MEDIAHDDVDROM, "media_hd_dvd_rom";
+    MediaHdDvdR = SIID_MEDIAHDDVDR, "media_hd_dvd_r";
+    MediaHdDvdRam = SIID_MEDIAHDDVDRAM, "media_hd_dvd_ram";
+    MediaBdRom = SIID_MEDIABDROM, "media_bd_rom";
+    MediaBdR = SIID_MEDIABDR, "media_bd_r";
+    MediaBdRe = SIID_MEDIABDRE, "media_bd_re";
+    ClusteredDrive = SIID_CLUSTEREDDRIVE, "clustered_drive";
+}
+
+/// Renders the large variant of a stock shell icon.
+#[cfg(windows)]
+pub fn extract_stock_icon(icon: StockIcon) -> Result<RgbaImage> {
+    unsafe {
+        let mut info: SHSTOCKICONINFO = std::mem::zeroed();
+        info.cbSize = std::mem::size_of::<SHSTOCKICONINFO>() as u32;
+
+        let hr = SHGetStockIconInfo(icon.siid(), SHGSI_ICON | SHGSI_LARGEICON, &mut info);
+        if hr < 0 || info.hIcon.is_null() {
+            anyhow::bail!(
+                "SHGetStockIconInfo failed for {}: 0x{:08X}",
+                icon.name(),
+                hr
+            );
+        }
+
+        let img = crate::gdi::hicon_to_image(info.hIcon);
+        DestroyIcon(info.hIcon);
+        img
+    }
+}
+
+/// Extracts the large version of a shell stock icon.
+/// Always fails with [`IconError::UnsupportedPlatform`] off Windows.
+#[cfg(not(windows))]
+pub fn extract_stock_icon(_icon: StockIcon) -> Result<RgbaImage> {
+    Err(IconError::UnsupportedPlatform.into())
+}
+
+/// Saves every stock icon available on this system as `<name>.png` in
+/// `out_dir`. Icons the running Windows version doesn't provide are skipped.
+pub fn export_all_stock_icons(out_dir: &Path) -> Result<Vec<(StockIcon, PathBuf)>> {
+    if cfg!(not(windows)) {
+        return Err(IconError::UnsupportedPlatform.into());
+    }
+
+    let mut exported = Vec::new();
+
+    for &icon in StockIcon::ALL {
+        let Ok(img) = extract_stock_icon(icon) else {
+            continue;
+        };
+
+        let output_path = out_dir.join(format!("{}.png", icon.name()));
+        img.save(&output_path)?;
+        exported.push((icon, output_path));
+    }
+
+    Ok(exported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_unique_file_stems() {
+        let mut names: Vec<_> = StockIcon::ALL.iter().map(|icon| icon.name()).collect();
+        names.sort_unstable();
+        names.dedup();
+        assert_eq!(names.len(), StockIcon::ALL.len());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn exports_one_file_per_available_stock_icon() {
+        let out_dir = tempfile::tempdir().unwrap();
+        let exported = export_all_stock_icons(out_dir.path()).unwrap();
+
+        // Every supported Windows version has the original SIID set, so only
+        // a few of the newest icons may be skipped.
+        assert!(
+            exported.len() > StockIcon::ALL.len() / 2,
+            "{}",
+            exported.len()
+        );
+        assert!(exported.iter().any(|&(icon, _)| icon == StockIcon::Folder));
+
+        let files = std::fs::read_dir(out_dir.path()).unwrap().count();
+        assert_eq!(files, exported.len());
+        for (icon, path) in &exported {
+            assert_eq!(path, &out_dir.path().join(format!("{}.png", icon.name())));
+            let img = image::open(path).unwrap();
+            assert!(img.width() > 0 && img.height() > 0);
+        }
+    }
+}
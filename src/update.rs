@@ -0,0 +1,373 @@
+use crate::icon_group::{IconImage, build_group, group_images_at, parse_group, parse_ico};
+use crate::pe::{Resource, ResourceKey, Resources};
+use crate::{IconError, check_icon_file, ordinal};
+use anyhow::{Context, Result};
+use object::pe::{RT_GROUP_ICON, RT_ICON};
+use std::collections::HashSet;
+use std::path::Path;
+#[cfg(windows)]
+use std::ptr::null_mut;
+#[cfg(windows)]
+use widestring::U16CString;
+#[cfg(windows)]
+use winapi::shared::minwindef::{FALSE, TRUE};
+#[cfg(windows)]
+use winapi::um::winbase::{BeginUpdateResourceW, EndUpdateResourceW, UpdateResourceW};
+#[cfg(windows)]
+use winapi::um::winuser::MAKEINTRESOURCEW;
+
+/// Replaces the icon group at `index` (in resource order, like the indices
+/// the extractors take) with the images of `new_icon`, the bytes of an ICO
+/// file. Every size in the ICO is written; the group's old images are reused
+/// or removed, and all other resources are left alone.
+pub fn replace_icon_in_exe(exe_path: &Path, new_icon: &[u8], index: u32) -> Result<()> {
+    let images = parse_ico(new_icon).context("Invalid replacement icon")?;
+    write_icon_group(exe_path, &images, index)
+}
+
+/// Copies the icon group at `source_index` of `source`, with all its sizes,
+/// over the group at `dest_index` of `dest`. `dest` is only modified once
+/// every resource has been staged, so a failure leaves it as it was.
+pub fn copy_icon_between_executables(
+    source: &Path,
+    source_index: u32,
+    dest: &Path,
+    dest_index: u32,
+) -> Result<()> {
+    let data = std::fs::read(check_icon_file(source)?)?;
+    let Some(images) = group_images_at(source, &data, source_index)? else {
+        return Err(IconError::NoIcon {
+            path: source.to_path_buf(),
+            index: ordinal(source_index)?,
+            count: None,
+        }
+        .into());
+    };
+    write_icon_group(dest, &images, dest_index)
+}
+
+/// Makes the icon group at `index` of `exe_path` hold `images`.
+fn write_icon_group(exe_path: &Path, images: &[IconImage], index: u32) -> Result<()> {
+    let data = std::fs::read(check_icon_file(exe_path)?)?;
+    let resources = Resources::parse_file(exe_path, &data)?;
+    let groups = match &resources {
+        Some(resources) => resources.of_type(RT_GROUP_ICON)?,
+        None => Vec::new(),
+    };
+    let icons = match &resources {
+        Some(resources) => resources.of_type(RT_ICON)?,
+        None => Vec::new(),
+    };
+
+    if index as usize >= groups.len() {
+        return Err(IconError::NoIcon {
+            path: exe_path.to_path_buf(),
+            index: ordinal(index)?,
+            count: Some(groups.len() as u32),
+        }
+        .into());
+    }
+
+    let changes = replace_group(&groups, index as usize, &icons, images)?;
+    apply_resource_changes(exe_path, &changes)
+}
+
+/// Removes every icon group and icon image, in all languages, leaving the
+/// file's other resources intact. Files without icons are left untouched.
+pub fn strip_icon_from_exe(exe_path: &Path) -> Result<()> {
+    let data = std::fs::read(check_icon_file(exe_path)?)?;
+    let Some(resources) = Resources::parse_file(exe_path, &data)? else {
+        return Ok(());
+    };
+
+    let mut changes = Vec::new();
+    for kind in [RT_GROUP_ICON, RT_ICON] {
+        for resource in resources.of_type_in_all_languages(kind)? {
+            changes.push(ResourceChange {
+                kind,
+                key: resource.key,
+                language: resource.language,
+                data: None,
+            });
+        }
+    }
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    apply_resource_changes(exe_path, &changes)
+}
+
+/// One resource to write, or to delete when `data` is `None`.
+// Only the Win32 writer reads the fields.
+#[cfg_attr(not(windows), allow(dead_code))]
+pub(crate) struct ResourceChange {
+    pub kind: u16,
+    pub key: ResourceKey,
+    pub language: u16,
+    pub data: Option<Vec<u8>>,
+}
+
+/// The changes that make `groups[index]` hold `images`. The group's own
+/// `RT_ICON` IDs are reused first, unless another group also refers to them,
+/// then unused ones are allocated; leftover old images are deleted unless
+/// another group still refers to them.
+fn replace_group(
+    groups: &[Resource],
+    index: usize,
+    icons: &[Resource],
+    images: &[IconImage],
+) -> Result<Vec<ResourceChange>> {
+    let group = &groups[index];
+    let mut old_ids: Vec<u16> = Vec::new();
+    for (_, id) in parse_group(group.data)? {
+        if !old_ids.contains(&id) {
+            old_ids.push(id);
+        }
+    }
+    let still_used: HashSet<u16> = groups
+        .iter()
+        .enumerate()
+        .filter(|&(i, _)| i != index)
+        .filter_map(|(_, other)| parse_group(other.data).ok())
+        .flatten()
+        .map(|(_, id)| id)
+        .collect();
+    let mut taken: HashSet<u16> = icons
+        .iter()
+        .filter_map(|icon| match icon.key {
+            ResourceKey::Id(id) => Some(id),
+            ResourceKey::Name(_) => None,
+        })
+        .chain(still_used.iter().copied())
+        .collect();
+
+    // Overwriting an image another group shares would change that group too.
+    let mut ids: Vec<u16> = old_ids
+        .iter()
+        .copied()
+        .filter(|id| !still_used.contains(id))
+        .take(images.len())
+        .collect();
+    taken.extend(&ids);
+    let mut next_id = 1u16;
+    while ids.len() < images.len() {
+        while taken.contains(&next_id) {
+            next_id = next_id
+                .checked_add(1)
+                .context("No free icon resource IDs left.")?;
+        }
+        taken.insert(next_id);
+        ids.push(next_id);
+    }
+
+    let existing = |id: u16| icons.iter().find(|icon| icon.key == ResourceKey::Id(id));
+    let language_of = |id: u16| existing(id).map_or(group.language, |icon| icon.language);
+
+    let mut changes: Vec<ResourceChange> = images
+        .iter()
+        .zip(&ids)
+        .map(|(image, &id)| ResourceChange {
+            kind: RT_ICON,
+            key: ResourceKey::Id(id),
+            language: language_of(id),
+            data: Some(image.data.to_vec()),
+        })
+        .collect();
+
+    for &id in &old_ids {
+        if !ids.contains(&id) && !still_used.contains(&id) && existing(id).is_some() {
+            changes.push(ResourceChange {
+                kind: RT_ICON,
+                key: ResourceKey::Id(id),
+                language: language_of(id),
+                data: None,
+            });
+        }
+    }
+
+    changes.push(ResourceChange {
+        kind: RT_GROUP_ICON,
+        key: group.key.clone(),
+        language: group.language,
+        data: Some(build_group(images, &ids)),
+    });
+
+    Ok(changes)
+}
+
+/// Writes `changes` into the file in one `BeginUpdateResourceW` session. Either
+/// all of them land or, if any fails, none do.
+#[cfg(windows)]
+pub(crate) fn apply_resource_changes(exe_path: &Path, changes: &[ResourceChange]) -> Result<()> {
+    let path = crate::to_extended_path(&check_icon_file(exe_path)?);
+
+    unsafe {
+        let update = BeginUpdateResourceW(path.as_ptr(), FALSE);
+        if update.is_null() {
+            anyhow::bail!("BeginUpdateResourceW failed for {}", exe_path.display());
+        }
+
+        for change in changes {
+            let name;
+            let name_ptr = match &change.key {
+                ResourceKey::Id(id) => MAKEINTRESOURCEW(*id),
+                ResourceKey::Name(key) => {
+                    name = U16CString::from_str(key)?;
+                    name.as_ptr() as *mut u16
+                }
+            };
+            let (data, size) = match &change.data {
+                Some(data) => (data.as_ptr() as *mut _, data.len() as u32),
+                None => (null_mut(), 0),
+            };
+
+            let updated = UpdateResourceW(
+                update,
+                MAKEINTRESOURCEW(change.kind),
+                name_ptr,
+                change.language,
+                data,
+                size,
+            );
+            if updated == 0 {
+                EndUpdateResourceW(update, TRUE);
+                anyhow::bail!(
+                    "UpdateResourceW failed for resource {} in {}",
+                    change.key,
+                    exe_path.display()
+                );
+            }
+        }
+
+        if EndUpdateResourceW(update, FALSE) == 0 {
+            anyhow::bail!("EndUpdateResourceW failed for {}", exe_path.display());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(not(windows))]
+pub(crate) fn apply_resource_changes(_exe_path: &Path, _changes: &[ResourceChange]) -> Result<()> {
+    Err(IconError::UnsupportedPlatform.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An icon group of 16x16 images stored as the `RT_ICON`s in `ids`.
+    fn group_data(ids: &[u16]) -> Vec<u8> {
+        let fields = [16, 16, 0, 0, 1, 0, 32, 0];
+        let images: Vec<IconImage> = ids
+            .iter()
+            .map(|_| IconImage {
+                fields: &fields,
+                data: &[],
+            })
+            .collect();
+        build_group(&images, ids)
+    }
+
+    fn resource(id: u16, data: &[u8]) -> Resource<'_> {
+        Resource {
+            key: ResourceKey::Id(id),
+            language: 1033,
+            rva: 0,
+            data,
+        }
+    }
+
+    /// Applies `changes` to in-memory resource lists of `kind`, keyed by ID.
+    fn apply(resources: &mut Vec<(u16, Vec<u8>)>, kind: u16, changes: &[ResourceChange]) {
+        for change in changes.iter().filter(|change| change.kind == kind) {
+            let ResourceKey::Id(id) = change.key else {
+                panic!("unexpected named resource");
+            };
+            resources.retain(|(existing, _)| *existing != id);
+            if let Some(data) = &change.data {
+                resources.push((id, data.clone()));
+            }
+        }
+        resources.sort();
+    }
+
+    #[test]
+    fn replace_group_round_trips() {
+        // Group 0 holds icons 1 and 2; group 1 shares icon 2 and has icon 3.
+        let group0 = group_data(&[1, 2]);
+        let group1 = group_data(&[2, 3]);
+        let groups = [resource(1, &group0), resource(2, &group1)];
+        let icons = [
+            resource(1, b"old1"),
+            resource(2, b"old2"),
+            resource(3, b"old3"),
+        ];
+        let fields = [32, 32, 0, 0, 1, 0, 32, 0];
+        let images = [
+            IconImage {
+                fields: &fields,
+                data: b"new-a",
+            },
+            IconImage {
+                fields: &fields,
+                data: b"new-b",
+            },
+        ];
+
+        let changes = replace_group(&groups, 0, &icons, &images).unwrap();
+
+        let mut icons_after: Vec<(u16, Vec<u8>)> = icons
+            .iter()
+            .map(|icon| match icon.key {
+                ResourceKey::Id(id) => (id, icon.data.to_vec()),
+                ResourceKey::Name(_) => unreachable!(),
+            })
+            .collect();
+        apply(&mut icons_after, RT_ICON, &changes);
+        let mut groups_after = vec![(1, group0.clone()), (2, group1.clone())];
+        apply(&mut groups_after, RT_GROUP_ICON, &changes);
+
+        // Icon 1 is reused, icon 2 is left to group 1, and the second image
+        // gets the first free ID.
+        assert_eq!(
+            icons_after,
+            [
+                (1, b"new-a".to_vec()),
+                (2, b"old2".to_vec()),
+                (3, b"old3".to_vec()),
+                (4, b"new-b".to_vec()),
+            ]
+        );
+        assert_eq!(groups_after[1], (2, group1));
+        let entries = parse_group(&groups_after[0].1).unwrap();
+        assert!(entries.iter().all(|(entry, _)| *entry == fields));
+        assert_eq!(
+            entries.iter().map(|&(_, id)| id).collect::<Vec<_>>(),
+            [1, 4]
+        );
+    }
+
+    #[test]
+    fn replace_group_deletes_unshared_leftovers() {
+        let group0 = group_data(&[1, 2, 3]);
+        let group1 = group_data(&[3]);
+        let groups = [resource(1, &group0), resource(2, &group1)];
+        let icons = [resource(1, b"1"), resource(2, b"2"), resource(3, b"3")];
+        let fields = [16, 16, 0, 0, 1, 0, 32, 0];
+        let images = [IconImage {
+            fields: &fields,
+            data: b"new",
+        }];
+
+        let changes = replace_group(&groups, 0, &icons, &images).unwrap();
+        let deleted: Vec<&ResourceKey> = changes
+            .iter()
+            .filter(|change| change.data.is_none())
+            .map(|change| &change.key)
+            .collect();
+
+        assert_eq!(deleted, [&ResourceKey::Id(2)]);
+    }
+}
@@ -0,0 +1,213 @@
+use crate::check_icon_file;
+use crate::ico_file::decode_image;
+use crate::icon_group::{IconImage, parse_group};
+use crate::pe::{ResourceKey, Resources};
+use anyhow::Result;
+use object::pe::{RT_GROUP_ICON, RT_ICON};
+use std::path::Path;
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Something unusual about an icon image. Only `MissingImage` and `Corrupt`
+/// stop the image from being extracted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum IconAnomaly {
+    /// The group refers to an `RT_ICON` resource the file doesn't have.
+    MissingImage,
+    /// The image data couldn't be decoded.
+    Corrupt(String),
+    /// The image is stored as PNG, which APIs older than Vista can't read.
+    PngEncoded,
+    /// A 32-bit image whose alpha channel is zero everywhere, so only the
+    /// AND mask decides transparency.
+    ZeroAlpha,
+    /// A monochrome image: it has no color bitmap, only the AND/XOR masks.
+    MissingColorBitmap,
+}
+
+/// The health of one image of one icon group.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct IconVerificationResult {
+    /// ID or name of the `RT_GROUP_ICON` resource.
+    pub group: String,
+    /// ID of the `RT_ICON` resource holding the image.
+    pub icon_id: u16,
+    /// Size of the decoded image, or `None` if it couldn't be decoded.
+    pub dimensions: Option<(u32, u32)>,
+    /// Everything wrong with the image; empty if it is healthy.
+    pub anomalies: Vec<IconAnomaly>,
+}
+
+/// Decodes every image of every icon group in the file and reports what was
+/// found, for auditing binaries. Problems with individual images are
+/// reported in the results rather than as errors.
+pub fn verify_icon_resources(file_path: &Path) -> Result<Vec<IconVerificationResult>> {
+    let data = std::fs::read(check_icon_file(file_path)?)?;
+    let Some(resources) = Resources::parse_file(file_path, &data)? else {
+        return Ok(Vec::new());
+    };
+    let icons = resources.of_type(RT_ICON)?;
+
+    let mut results = Vec::new();
+    for group in resources.of_type(RT_GROUP_ICON)? {
+        let entries = match parse_group(group.data) {
+            Ok(entries) => entries,
+            Err(e) => {
+                results.push(IconVerificationResult {
+                    group: group.key.to_string(),
+                    icon_id: 0,
+                    dimensions: None,
+                    anomalies: vec![IconAnomaly::Corrupt(format!("{e:#}"))],
+                });
+                continue;
+            }
+        };
+
+        for (fields, icon_id) in entries {
+            let image = icons
+                .iter()
+                .find(|icon| icon.key == ResourceKey::Id(icon_id))
+                .map(|icon| IconImage {
+                    fields,
+                    data: icon.data,
+                });
+            let (dimensions, anomalies) = match image {
+                Some(image) => verify_image(&image),
+                None => (None, vec![IconAnomaly::MissingImage]),
+            };
+            results.push(IconVerificationResult {
+                group: group.key.to_string(),
+                icon_id,
+                dimensions,
+                anomalies,
+            });
+        }
+    }
+
+    Ok(results)
+}
+
+fn verify_image(image: &IconImage) -> (Option<(u32, u32)>, Vec<IconAnomaly>) {
+    let mut anomalies = Vec::new();
+
+    if image.data.starts_with(PNG_SIGNATURE) {
+        anomalies.push(IconAnomaly::PngEncoded);
+    } else {
+        match bitmap_bit_count(image.data) {
+            Some(1) => anomalies.push(IconAnomaly::MissingColorBitmap),
+            Some(32) if bitmap_alpha_is_zero(image.data) => anomalies.push(IconAnomaly::ZeroAlpha),
+            _ => {}
+        }
+    }
+
+    let dimensions = match decode_image(image) {
+        Ok(img) => Some(img.dimensions()),
+        Err(e) => {
+            anomalies.push(IconAnomaly::Corrupt(e.to_string()));
+            None
+        }
+    };
+
+    (dimensions, anomalies)
+}
+
+/// `biBitCount` of the `BITMAPINFOHEADER` a BMP icon image starts with.
+fn bitmap_bit_count(data: &[u8]) -> Option<u16> {
+    let bytes = data.get(14..16)?;
+    Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+/// Whether every alpha byte of a 32-bit BMP icon's XOR bitmap is zero. The
+/// header's height covers both the XOR bitmap and the AND mask.
+fn bitmap_alpha_is_zero(data: &[u8]) -> bool {
+    let read_u32 = |at: usize| {
+        data.get(at..at + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    };
+    let (Some(header_size), Some(width), Some(height)) = (read_u32(0), read_u32(4), read_u32(8))
+    else {
+        return false;
+    };
+
+    let start = header_size as usize;
+    let Some(xor) = (width as usize)
+        .checked_mul(height as usize / 2)
+        .and_then(|pixels| pixels.checked_mul(4))
+        .and_then(|len| start.checked_add(len))
+        .and_then(|end| data.get(start..end))
+    else {
+        return false;
+    };
+    xor.chunks_exact(4).all(|pixel| pixel[3] == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ico_file::fixture::{bmp_image, mono_bmp_image, png_image};
+    use crate::icon_group::build_group;
+    use crate::pe::fixture::{build_pe, write_temp};
+
+    #[test]
+    fn reports_each_kind_of_anomaly() {
+        let entry = |size: u8, bits: u8| [size, size, 0, 0, 1, 0, bits, 0];
+        let (healthy, png, zero_alpha) = (bmp_image(16, 0xff), png_image(32), bmp_image(16, 0));
+        let mono = mono_bmp_image(32);
+        let (fields16, fields32, mono_fields) = (entry(16, 32), entry(32, 32), entry(32, 1));
+        let image = |fields, data| IconImage { fields, data };
+        let images = [
+            image(&fields16, &healthy),
+            image(&fields32, &png),
+            image(&fields16, &zero_alpha),
+            image(&mono_fields, &mono),
+            image(&fields16, b"not a bitmap"),
+            image(&fields16, &healthy),
+        ];
+        let group = build_group(&images, &[1, 2, 3, 4, 5, 9]);
+        let truncated = &group[..group.len() - 1];
+        let pe = build_pe(&[
+            (RT_ICON, 1, &healthy),
+            (RT_ICON, 2, &png),
+            (RT_ICON, 3, &zero_alpha),
+            (RT_ICON, 4, &mono),
+            (RT_ICON, 5, b"not a bitmap"),
+            (RT_GROUP_ICON, 1, &group),
+            (RT_GROUP_ICON, 2, truncated),
+        ]);
+        let (_dir, path) = write_temp("app.exe", &pe);
+
+        let results = verify_icon_resources(&path).unwrap();
+
+        let summary: Vec<_> = results
+            .iter()
+            .map(|r| (r.group.as_str(), r.icon_id, r.dimensions))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("1", 1, Some((16, 16))),
+                ("1", 2, Some((32, 32))),
+                ("1", 3, Some((16, 16))),
+                ("1", 4, Some((32, 32))),
+                ("1", 5, None),
+                ("1", 9, None),
+                ("2", 0, None),
+            ]
+        );
+        assert_eq!(results[0].anomalies, []);
+        assert_eq!(results[1].anomalies, [IconAnomaly::PngEncoded]);
+        assert_eq!(results[2].anomalies, [IconAnomaly::ZeroAlpha]);
+        assert_eq!(results[3].anomalies, [IconAnomaly::MissingColorBitmap]);
+        assert!(matches!(
+            results[4].anomalies[..],
+            [IconAnomaly::Corrupt(_)]
+        ));
+        assert_eq!(results[5].anomalies, [IconAnomaly::MissingImage]);
+        assert!(matches!(
+            &results[6].anomalies[..],
+            [IconAnomaly::Corrupt(reason)] if reason.contains("truncated")
+        ));
+    }
+}